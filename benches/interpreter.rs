@@ -0,0 +1,55 @@
+//! Instructions per second of the interpreter on a register and memory heavy loop.
+//!
+//! cargo bench --bench interpreter
+
+use std::time::Instant;
+
+use mmvm::interpreter::Interpreter;
+use mmvm::utils::header::Header;
+
+// mov bx, 0100 / xor si, si / mov cx, count
+// l: mov ax, [bx+si] / add ax, cx / mov [bx+si], ax / push ax / pop dx / loop l
+// hlt
+fn program(count: u16) -> Vec<u8> {
+    let [low, high] = count.to_le_bytes();
+    let text = [
+        0xbb, 0x00, 0x01, 0x31, 0xf6, 0xb9, low, high, 0x8b, 0x00, 0x01, 0xc8, 0x89, 0x00, 0x50,
+        0x5a, 0xe2, 0xf6, 0xf4,
+    ];
+    let mut header = vec![0x01, 0x03, 0x00, 0x04, 0x20, 0x00, 0x00, 0x00];
+    for long in [text.len() as u32, 0, 0, 0, 0, 0] {
+        header.extend(long.to_le_bytes());
+    }
+    [header, text.to_vec()].concat()
+}
+
+fn run(count: u16) -> (u64, f64) {
+    let bytes_data = program(count);
+    let header = Header::new(&bytes_data).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.trace = false;
+    interpreter
+        .load(&header, &bytes_data, &["bench".to_owned()], &[])
+        .unwrap();
+
+    let mut steps = 0u64;
+    let start = Instant::now();
+    while !interpreter.hardware.halted {
+        interpreter.step().unwrap();
+        steps += 1;
+    }
+    (steps, start.elapsed().as_secs_f64())
+}
+
+fn main() {
+    for count in [0x0400, 0x1000, 0x4000] {
+        let (steps, seconds) = run(count);
+        println!(
+            "loop x{:<6} {:>8} instructions {:>10.3} s {:>14.0} instructions/s",
+            count,
+            steps,
+            seconds,
+            steps as f64 / seconds
+        );
+    }
+}
@@ -10,9 +10,69 @@ pub struct Args {
     #[arg(short, long, value_name = "FILE")]
     pub d: Option<PathBuf>,
 
+    /// Input file to interpret
     #[arg(short, long, value_name = "FILE")]
     pub m: Option<PathBuf>,
 
+    /// Input file whose a.out header to dump
+    #[arg(long, value_name = "FILE")]
+    pub header: Option<PathBuf>,
+
+    /// Environment variable passed to the interpreted program, can be repeated
+    #[arg(long, value_name = "KEY=VAL", value_parser = parse_env)]
+    pub env: Vec<String>,
+
+    /// Directory the interpreted program sees as `/`, its file system calls can't leave it
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Arguments passed to the interpreted program
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
+
     #[arg(long, action = clap::ArgAction::Count)]
     debug: u8,
 }
+
+fn parse_env(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(value.to_owned()),
+        _ => Err(format!("`{}` should be in KEY=VAL form", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::error::ErrorKind;
+    use clap::Parser;
+
+    use crate::cli::args::Args;
+
+    #[test]
+    fn test_parse_env() {
+        let args = Args::try_parse_from(["mmvm", "--env", "HOME=/", "--env", "A=", "-m", "a.out"])
+            .unwrap();
+        assert_eq!(args.env, vec!["HOME=/", "A="]);
+        assert_eq!(args.m, Some(PathBuf::from("a.out")));
+
+        // no `=` or no key is rejected before the program runs
+        for (i, value) in ["HOME", "=/"].into_iter().enumerate() {
+            let error = Args::try_parse_from(["mmvm", "--env", value, "-m", "a.out"]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ValueValidation, "#{}", i);
+        }
+    }
+
+    #[test]
+    fn test_parse_trailing_args() {
+        // everything after the binary path belongs to the program, flags included
+        let args = Args::try_parse_from(["mmvm", "-m", "a.out", "x", "-m", "--env", "y"]).unwrap();
+        assert_eq!(args.m, Some(PathBuf::from("a.out")));
+        assert_eq!(args.args, vec!["x", "-m", "--env", "y"]);
+        assert!(args.env.is_empty());
+
+        let args = Args::try_parse_from(["mmvm", "-m", "a.out"]).unwrap();
+        assert!(args.args.is_empty());
+    }
+}
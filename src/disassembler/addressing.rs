@@ -22,6 +22,17 @@ pub enum Addressing {
 }
 
 impl Addressing {
+    // operands that go through a segment, the ones an override prefix applies to
+    pub fn is_memory(&self) -> bool {
+        matches!(
+            self,
+            Addressing::DirectAddressing(_)
+                | Addressing::BasedAddressing(_, _)
+                | Addressing::IndexedAddressing(_, _)
+                | Addressing::BasedIndexedAddressing(_, _, _)
+        )
+    }
+
     pub fn decode_displacement(
         r#mod: u8,
         r_m: u8,
@@ -163,7 +174,7 @@ impl Addressing {
             }
             0b00000111 => {
                 let reg = binary_data[0] & mask;
-                if let Some(register) = Register::decode(true, true, reg) {
+                if let Some(register) = Register::decode(w == 0b1, true, reg) {
                     (0, Some(Addressing::RegisterAddressing(register)), None)
                 } else {
                     (0, None, None)
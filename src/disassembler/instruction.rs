@@ -6,15 +6,25 @@ use crate::disassembler::mnemonic::Mnemonic;
 use crate::disassembler::mnemonic::Mnemonic::*;
 use crate::disassembler::numerical::{Immediate, Numerical};
 use crate::disassembler::register::ByteRegister::CL;
-use crate::disassembler::register::Register;
 use crate::disassembler::register::WordRegister::{AX, DX};
+use crate::disassembler::register::{Register, SegmentRegister};
+use crate::error::MmvmError;
 
 fn match_reg(binary_data: u8, reference: &[u8]) -> bool {
     let reg = (binary_data & 0b00111000) >> 3;
     reference.contains(&reg)
 }
 
-#[derive(Debug, Copy, Clone)]
+// the w bit, MOV (Immediate to Register) keeps it in bit 3 and segment registers are always words
+fn word_mode(opcode: u8) -> bool {
+    match opcode {
+        0b10110000..=0b10111111 => (opcode & 0b00001000) == 0b00001000,
+        0b10001100 | 0b10001110 => true,
+        _ => (opcode & 0b00000001) == 0b00000001,
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Instruction {
     Standalone(Mnemonic),
     WithInstruction(Mnemonic, Mnemonic),
@@ -22,6 +32,8 @@ pub enum Instruction {
     AddressToAddress(Mnemonic, Direction, Addressing, Addressing),
     WithImmediate(Mnemonic, Numerical),
     ImmediateToAddress(Mnemonic, Addressing, Numerical),
+    // segment override prefix (ES: / CS: / SS: / DS:) in front of another instruction
+    WithSegment(SegmentRegister, Box<Instruction>),
     Undefined,
 }
 
@@ -115,7 +127,8 @@ impl Instruction {
             }
             // Memory <-> Accumulator
             0b10100000 | 0b10100001 | 0b10100010 | 0b10100011 => {
-                let d = (binary_data[0] & 0b00000010) >> 1;
+                // unlike the ModRM forms, a clear bit 1 loads the accumulator
+                let d = ((binary_data[0] & 0b00000010) >> 1) ^ 0b1;
                 let w = binary_data[0] & 0b00000001;
                 if let (_, Some(address)) = Self::decode_data(true, false, &binary_data[1..]) {
                     (
@@ -168,7 +181,7 @@ impl Instruction {
             }
             // PUSH (Register)
             0b01010000..=0b01010111 => {
-                if let (l, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
+                if let (l, Some(reg), None) = Addressing::decode(0b1, binary_data, 0b00000111) {
                     (1 + l, Some(Instruction::WithAddress(PUSH, reg)))
                 } else {
                     (0, Some(Instruction::Undefined))
@@ -184,7 +197,7 @@ impl Instruction {
             }
             // POP (Register)
             0b01011000..=0b01011111 => {
-                if let (l, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
+                if let (l, Some(reg), None) = Addressing::decode(0b1, binary_data, 0b00000111) {
                     (1 + l, Some(Instruction::WithAddress(POP, reg)))
                 } else {
                     (0, Some(Instruction::Undefined))
@@ -231,7 +244,7 @@ impl Instruction {
             }
             // Register with Accumulator
             0b10010000..=0b10010111 => {
-                if let (l, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
+                if let (l, Some(reg), None) = Addressing::decode(0b1, binary_data, 0b00000111) {
                     (
                         1 + l,
                         Some(Instruction::AddressToAddress(
@@ -258,7 +271,7 @@ impl Instruction {
         };
         match binary_data[0] {
             // Fixed Port
-            0b11100100 | 0b11100101 => (
+            0b11100100..=0b11100111 => (
                 2,
                 Some(Instruction::ImmediateToAddress(
                     instruction,
@@ -268,12 +281,12 @@ impl Instruction {
                     Numerical::Imme(Immediate::from(&binary_data[1..2], false)),
                 )),
             ),
-            // Variable Port
-            0b11101100 | 0b11101101 => (
+            // Variable Port, OUT writes to the port in DX
+            0b11101100..=0b11101111 => (
                 1,
                 Some(Instruction::AddressToAddress(
                     instruction,
-                    Direction::FromReg,
+                    Direction::from((binary_data[0] & 0b00000010) >> 1),
                     Addressing::RegisterAddressing(Register::WordReg(DX)),
                     Addressing::RegisterAddressing(
                         Register::decode(w == 0b1, true, 0b000).unwrap(),
@@ -446,7 +459,7 @@ impl Instruction {
     }
 
     pub fn decode_multiply_divide_instruction(binary_data: &[u8]) -> (usize, Option<Instruction>) {
-        if binary_data[0] == 0b11110110 | 0b11110111 {
+        if matches!(binary_data[0], 0b11110110 | 0b11110111) {
             if let (l, None, Some(r_m)) =
                 Addressing::decode(binary_data[0] & 0b1, &binary_data[1..], 0b11000111)
             {
@@ -466,11 +479,11 @@ impl Instruction {
     }
 
     pub fn decode_not_instruction(binary_data: &[u8]) -> (usize, Option<Instruction>) {
-        if binary_data[0] == 0b11110110 | 0b11110111 {
+        if matches!(binary_data[0], 0b11110110 | 0b11110111) {
             if let (l, None, Some(r_m)) =
                 Addressing::decode(binary_data[0] & 0b1, &binary_data[1..], 0b11000111)
             {
-                if (binary_data[1] & 0b00111000) == 0b00011000 {
+                if (binary_data[1] & 0b00111000) == 0b00010000 {
                     (2 + l, Some(Instruction::WithAddress(NOT, r_m)))
                 } else {
                     (0, Some(Instruction::Undefined))
@@ -560,9 +573,10 @@ impl Instruction {
                     0b00110100 => XOR,
                     _ => return (0, Some(Instruction::Undefined)),
                 };
-                if let (l, Some(immediate)) = Self::decode_data(w == 0b1, true, &binary_data[1..]) {
+                if let (l, Some(immediate)) = Self::decode_data(w == 0b1, false, &binary_data[1..])
+                {
                     (
-                        2 + l,
+                        1 + l,
                         Some(Instruction::ImmediateToAddress(
                             instruction,
                             Addressing::RegisterAddressing(
@@ -577,11 +591,14 @@ impl Instruction {
             }
             // Immediate to Register/Memory
             0b10000000..=0b10000011 => {
+                let s = (binary_data[0] & 0b00000010) >> 1;
                 if let (rl, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111)
                 {
-                    if let (dl, Some(immediate)) =
-                        Self::decode_data(w == 0b1, false, &binary_data[(2 + rl)..])
-                    {
+                    if let (dl, Some(immediate)) = Self::decode_data(
+                        (w == 0b1) & (s != 0b1),
+                        (s == 0b1) & ((binary_data[2 + rl] as i8) < 0i8),
+                        &binary_data[(2 + rl)..],
+                    ) {
                         let instruction = match binary_data[1] & 0b00111000 {
                             0b00100000 => AND,
                             0b00001000 => OR,
@@ -695,14 +712,16 @@ impl Instruction {
     }
 
     pub fn decode_repeat_instruction(binary_data: &[u8]) -> (usize, Option<Instruction>) {
-        let instruction = match binary_data[0] {
-            0b11110010 => REP,
-            0b11110011 => REPNE,
-            _ => return (0, Some(Instruction::Undefined)),
-        };
         if let (l, Some(Instruction::Standalone(sub_instruction))) =
             Self::decode_string_instruction(&binary_data[1..])
         {
+            // F3 is REP / REPE and F2 is REPNE, the ZF condition only applies to CMPS and SCAS
+            let instruction = match (binary_data[0], sub_instruction) {
+                (0b11110010 | 0b11110011, MOVSB | MOVSW | LODSB | LODSW | STOSB | STOSW) => REP,
+                (0b11110011, _) => REPE,
+                (0b11110010, _) => REPNE,
+                _ => return (0, Some(Instruction::Undefined)),
+            };
             (
                 1 + l,
                 Some(Instruction::WithInstruction(instruction, sub_instruction)),
@@ -725,10 +744,7 @@ impl Instruction {
                     return (0, Some(Instruction::Undefined));
                 }
                 let immediate = u16::from_le_bytes((&binary_data[1..3]).try_into().unwrap());
-                let displacement = match binary_data[2] & 0x80 {
-                    0x0 => (pc as u16) + 0x3 + immediate,
-                    _ => ((pc as i16) + 3i16 - ((immediate as i16) * -1i16)) as u16,
-                };
+                let displacement = pc.wrapping_add(0x3).wrapping_add(immediate);
                 (
                     3,
                     Some(Instruction::WithImmediate(
@@ -742,10 +758,9 @@ impl Instruction {
                 if binary_data.len() < 2 {
                     return (0, Some(Instruction::Undefined));
                 }
-                let displacement = match binary_data[1] & 0x80 {
-                    0x0 => (pc as u16) + 0x2 + (binary_data[1] as u16),
-                    _ => (pc as u16) + 0x2 - ((!(binary_data[1] - 0b1)) as u16),
-                };
+                let displacement = pc
+                    .wrapping_add(0x2)
+                    .wrapping_add(binary_data[1] as i8 as u16);
                 (
                     2,
                     Some(Instruction::WithImmediate(
@@ -757,12 +772,18 @@ impl Instruction {
             // CALL / JMP (Indirect within Segment / Indirect Intersegment)
             0b11111111 if match_reg(binary_data[1], &[0b010, 0b011, 0b100, 0b101]) => {
                 let instruction = match (binary_data[1] & 0b00111000) >> 3 {
-                    0b010 | 0b011 => CALL,
-                    0b100 | 0b101 => JMP,
+                    0b010 => CALL,
+                    0b011 => CALLFAR,
+                    0b100 => JMP,
+                    0b101 => JMPFAR,
                     _ => return (0, Some(Instruction::Undefined)),
                 };
                 if let (l, None, Some(r_m)) = Addressing::decode(0b1, &binary_data[1..], 0b11000111)
                 {
+                    // a far pointer can only be loaded from memory
+                    if matches!(instruction, CALLFAR | JMPFAR) && !r_m.is_memory() {
+                        return (0, Some(Instruction::Undefined));
+                    }
                     (2 + l, Some(Instruction::WithAddress(instruction, r_m)))
                 } else {
                     (0, Some(Instruction::Undefined))
@@ -771,9 +792,9 @@ impl Instruction {
             // Direct Intersegment
             0b10011010 | 0b11101010 => {
                 let instruction = if binary_data[0] == 0b10011010 {
-                    CALL
+                    CALLFAR
                 } else {
-                    JMP
+                    JMPFAR
                 };
                 if let (_, Some(offset)) = Self::decode_data(true, false, &binary_data[1..]) {
                     if let (_, Some(segment)) = Self::decode_data(true, false, &binary_data[3..]) {
@@ -811,7 +832,7 @@ impl Instruction {
             0b11001011 => (1, Some(Instruction::Standalone(RETF))),
             // Intersegment Adding Immediate to SP
             0b11001010 => {
-                if let (_, Some(data)) = Self::decode_data(true, true, &binary_data[1..]) {
+                if let (_, Some(data)) = Self::decode_data(true, false, &binary_data[1..]) {
                     (3, Some(Instruction::WithImmediate(RETF, data)))
                 } else {
                     (0, Some(Instruction::Undefined))
@@ -848,10 +869,9 @@ impl Instruction {
             0b11100011 => JCXZ,
             _ => return (0, Some(Instruction::Undefined)),
         };
-        let displacement = match binary_data[1] & 0x80 {
-            0b0 => (pc as i16) + 2i16 + (binary_data[1] as i16),
-            _ => (pc as i16) + 2i16 - (((binary_data[1] as i8) * -1i8) as i16),
-        } as u16;
+        let displacement = pc
+            .wrapping_add(0x2)
+            .wrapping_add(binary_data[1] as i8 as u16);
         (
             2,
             Some(Instruction::WithImmediate(
@@ -885,10 +905,78 @@ impl Instruction {
         }
     }
 
-    pub fn decode(pc: u16, binary_data: &[u8]) -> (usize, Option<Instruction>) {
+    pub fn decode(pc: u16, binary_data: &[u8]) -> Result<(usize, Instruction), MmvmError> {
+        Self::decode_with_size(pc, binary_data)
+            .map(|(length, instruction, _)| (length, instruction))
+    }
+
+    // Decode like decode, along with the w bit of the opcode. The interpreter needs it where the
+    // operands don't show the size, a memory operand alone doesn't tell inc byte [bx] from a word
+    pub fn decode_with_size(
+        pc: u16,
+        binary_data: &[u8],
+    ) -> Result<(usize, Instruction, bool), MmvmError> {
         if binary_data.is_empty() {
-            panic!("data length not enough");
+            return Err(MmvmError::TruncatedInput {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        // REP / REPE / REPNE ahead of a segment prefix, decoded as if the segment prefix came first
+        if let (0b11110010 | 0b11110011, Some(0b00100110 | 0b00101110 | 0b00110110 | 0b00111110)) =
+            (binary_data[0], binary_data.get(1))
+        {
+            let mut swapped = binary_data.to_vec();
+            swapped.swap(0, 1);
+            return Self::decode_with_size(pc, &swapped).map_err(|error| match error {
+                MmvmError::UndecodableOpcode { address, .. } => MmvmError::UndecodableOpcode {
+                    address,
+                    bytes: binary_data[..binary_data.len().min(6)].to_vec(),
+                },
+                error => error,
+            });
+        }
+        // 001ss110, the prefix is decoded together with the instruction it applies to
+        if let 0b00100110 | 0b00101110 | 0b00110110 | 0b00111110 = binary_data[0] {
+            let segment = SegmentRegister::from((binary_data[0] & 0b00011000) >> 3);
+            return match Self::decode_with_size(pc.wrapping_add(1), &binary_data[1..]) {
+                Ok((length, instruction, word_mode)) => Ok((
+                    1 + length,
+                    Instruction::WithSegment(segment, Box::new(instruction)),
+                    word_mode,
+                )),
+                Err(MmvmError::TruncatedInput { expected, actual }) => {
+                    Err(MmvmError::TruncatedInput {
+                        expected: 1 + expected,
+                        actual: 1 + actual,
+                    })
+                }
+                Err(_) => Err(MmvmError::UndecodableOpcode {
+                    address: pc,
+                    bytes: binary_data[..binary_data.len().min(6)].to_vec(),
+                }),
+            };
+        }
+        // the longest instruction is 6 bytes, pad short input so no decoder reads past its end
+        let available = binary_data.len().min(6);
+        let mut chunk = [0x00; 6];
+        chunk[..available].copy_from_slice(&binary_data[..available]);
+        match Self::decode_opcode(pc, &chunk) {
+            (0, _) | (_, None) | (_, Some(Instruction::Undefined)) => {
+                Err(MmvmError::UndecodableOpcode {
+                    address: pc,
+                    bytes: chunk[..available].to_vec(),
+                })
+            }
+            (length, Some(_)) if length > binary_data.len() => Err(MmvmError::TruncatedInput {
+                expected: length,
+                actual: binary_data.len(),
+            }),
+            (length, Some(instruction)) => Ok((length, instruction, word_mode(chunk[0]))),
         }
+    }
+
+    fn decode_opcode(pc: u16, binary_data: &[u8]) -> (usize, Option<Instruction>) {
         match binary_data[0] {
             0b11010111 => (1, Some(Instruction::Standalone(XLAT))),
             0b10011111 => (1, Some(Instruction::Standalone(LAHF))),
@@ -896,7 +984,7 @@ impl Instruction {
             0b10011100 => (1, Some(Instruction::Standalone(PUSHF))),
             0b10011101 => (1, Some(Instruction::Standalone(POPF))),
             0b00110111 => (1, Some(Instruction::Standalone(AAA))),
-            0b00100111 => (1, Some(Instruction::Standalone(BAA))),
+            0b00100111 => (1, Some(Instruction::Standalone(DAA))),
             0b00111111 => (1, Some(Instruction::Standalone(AAS))),
             0b00101111 => (1, Some(Instruction::Standalone(DAS))),
             0b10011000 => (1, Some(Instruction::Standalone(CBW))),
@@ -917,8 +1005,24 @@ impl Instruction {
             0b11110100 => (1, Some(Instruction::Standalone(HLT))),
             0b10011011 => (1, Some(Instruction::Standalone(WAIT))),
             0b11110000 => (1, Some(Instruction::Standalone(LOCK))),
-            0b11010101 if binary_data[1] == 0b00001010 => (2, Some(Instruction::Standalone(AAD))),
-            0b11010100 if binary_data[1] == 0b00001010 => (2, Some(Instruction::Standalone(AAM))),
+            // AAM / AAD, the second byte is the base and only shown when it isn't 10
+            0b11010100 | 0b11010101 => {
+                let mnemonic = if binary_data[0] == 0b11010100 {
+                    AAM
+                } else {
+                    AAD
+                };
+                match binary_data[1] {
+                    0b00001010 => (2, Some(Instruction::Standalone(mnemonic))),
+                    base => (
+                        2,
+                        Some(Instruction::WithImmediate(
+                            mnemonic,
+                            Numerical::Imme(Immediate::UnsignedByte(base)),
+                        )),
+                    ),
+                }
+            }
             // MOV (Register/Memory to Segment Register / Segment Register to Register/Memory)
             0b10001110 | 0b10001100 if (binary_data[1] & 0b00100000) == 0b0 => {
                 Self::decode_move_instruction(binary_data)
@@ -1050,7 +1154,7 @@ impl Instruction {
             | 0b00110100
             | 0b00110101 => Self::decode_logic_instruction(binary_data),
             // AND / OR / XOR (Immediate to Register/Memory)
-            0b10000000 | 0b10000001 if match_reg(binary_data[1], &[0b001, 0b100, 0b110]) => {
+            0b10000000..=0b10000011 if match_reg(binary_data[1], &[0b001, 0b100, 0b110]) => {
                 Self::decode_logic_instruction(binary_data)
             }
             // TEST (Register/Memory and Register, Immediate Data and Accumulator)
@@ -1071,25 +1175,52 @@ impl Instruction {
     }
 }
 
-impl Display for Instruction {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl Instruction {
+    // an override prefix shows up on the memory operand, or in front when there is none
+    fn write(&self, f: &mut Formatter<'_>, segment: Option<SegmentRegister>) -> std::fmt::Result {
+        let operand = |address: &Addressing| match segment {
+            Some(segment) if address.is_memory() => format!("{}:{}", segment, address),
+            _ => format!("{}", address),
+        };
+        let has_memory = match self {
+            Instruction::WithAddress(_, target)
+            | Instruction::ImmediateToAddress(_, target, _)
+            | Instruction::AddressToAddress(_, _, _, target) => target.is_memory(),
+            _ => false,
+        };
+        if let (Some(segment), false) = (segment, has_memory) {
+            write!(f, "{}: ", segment)?;
+        }
         match self {
             &Instruction::Standalone(mnemonic) => write!(f, "{}", mnemonic),
             &Instruction::WithInstruction(mnemonic, sub_mnemonic) => {
                 write!(f, "{} {}", mnemonic, sub_mnemonic)
             }
-            &Instruction::WithAddress(mnemonic, target) => write!(f, "{} {}", mnemonic, target),
+            &Instruction::WithAddress(mnemonic, target) => {
+                write!(f, "{} {}", mnemonic, operand(&target))
+            }
             &Instruction::AddressToAddress(mnemonic, direction, reg, r_m) => match direction {
-                Direction::FromReg => write!(f, "{} {}, {}", mnemonic, r_m, reg),
-                Direction::ToReg => write!(f, "{} {}, {}", mnemonic, reg, r_m),
+                Direction::FromReg => write!(f, "{} {}, {}", mnemonic, operand(&r_m), reg),
+                Direction::ToReg => write!(f, "{} {}, {}", mnemonic, reg, operand(&r_m)),
             },
             &Instruction::WithImmediate(mnemonic, immediate) => {
                 write!(f, "{} {}", mnemonic, immediate)
             }
+            // the port comes first
+            &Instruction::ImmediateToAddress(OUT, target, immediate) => {
+                write!(f, "{} {}, {}", OUT, immediate, operand(&target))
+            }
             &Instruction::ImmediateToAddress(mnemonic, target, immediate) => {
-                write!(f, "{} {}, {}", mnemonic, target, immediate)
+                write!(f, "{} {}, {}", mnemonic, operand(&target), immediate)
             }
+            Instruction::WithSegment(segment, instruction) => instruction.write(f, Some(*segment)),
             &Instruction::Undefined => write!(f, "(undefined)"),
         }
     }
 }
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write(f, None)
+    }
+}
@@ -21,7 +21,7 @@ pub enum Mnemonic {
     ADC,
     INC,
     AAA,
-    BAA,
+    DAA,
     SUB,
     SSB,
     DEC,
@@ -52,6 +52,7 @@ pub enum Mnemonic {
     OR,
     XOR,
     REP,
+    REPE,
     REPNE,
     MOVSB,
     MOVSW,
@@ -64,7 +65,9 @@ pub enum Mnemonic {
     STOSB,
     STOSW,
     CALL,
+    CALLFAR,
     JMP,
+    JMPFAR,
     JMPSHORT,
     RET,
     RETF,
@@ -126,7 +129,7 @@ impl Display for Mnemonic {
             &Mnemonic::ADC => "adc",
             &Mnemonic::INC => "inc",
             &Mnemonic::AAA => "aaa",
-            &Mnemonic::BAA => "baa",
+            &Mnemonic::DAA => "daa",
             &Mnemonic::SUB => "sub",
             &Mnemonic::SSB => "sbb",
             &Mnemonic::DEC => "dec",
@@ -157,6 +160,7 @@ impl Display for Mnemonic {
             &Mnemonic::OR => "or",
             &Mnemonic::XOR => "xor",
             &Mnemonic::REP => "rep",
+            &Mnemonic::REPE => "repe",
             &Mnemonic::REPNE => "repne",
             &Mnemonic::MOVSB => "movsb",
             &Mnemonic::MOVSW => "movsw",
@@ -169,7 +173,9 @@ impl Display for Mnemonic {
             &Mnemonic::STOSB => "stosb",
             &Mnemonic::STOSW => "stosw",
             &Mnemonic::CALL => "call",
+            &Mnemonic::CALLFAR => "call far",
             &Mnemonic::JMP => "jmp",
+            &Mnemonic::JMPFAR => "jmp far",
             &Mnemonic::JMPSHORT => "jmp short",
             &Mnemonic::RET => "ret",
             &Mnemonic::RETF => "retf",
@@ -1,5 +1,9 @@
-use crate::{disassembler::instruction::Instruction, utils::header::Header};
-use std::cmp;
+use std::collections::BTreeMap;
+
+use crate::disassembler::mnemonic::Mnemonic::{RET, RETF};
+use crate::disassembler::numerical::{Immediate, Numerical};
+use crate::utils::symbol::{read_symbols, N_TEXT};
+use crate::{disassembler::instruction::Instruction, error::MmvmError, utils::header::Header};
 
 pub mod addressing;
 pub mod direction;
@@ -11,54 +15,83 @@ mod test;
 
 pub struct Disassembler {
     pub asm: Vec<(u16, Vec<u8>, Instruction)>,
+    // text symbols by address, several names can share one address
+    pub labels: BTreeMap<u16, Vec<String>>,
 }
 
 impl Disassembler {
     pub fn new() -> Self {
-        Self { asm: Vec::new() }
+        Self {
+            asm: Vec::new(),
+            labels: BTreeMap::new(),
+        }
     }
 
-    pub fn disassemble(&mut self, header: Header, bytes_data: &[u8]) {
+    pub fn disassemble(&mut self, header: Header, bytes_data: &[u8]) -> Result<(), MmvmError> {
         let (a_hdrlen, a_text) = (header.a_hdrlen as usize, header.a_text as usize);
-        let decode_area = &bytes_data[a_hdrlen..];
+        if bytes_data.len() < a_hdrlen + a_text {
+            return Err(MmvmError::TruncatedInput {
+                expected: a_hdrlen + a_text,
+                actual: bytes_data.len(),
+            });
+        }
+        let decode_area = &bytes_data[a_hdrlen..(a_hdrlen + a_text)];
         let mut pc: usize = 0;
-        loop {
+        while pc < a_text {
             let chunk = &decode_area[pc..];
-            if let (length, Some(instruction)) = Instruction::decode(pc as u16, chunk) {
-                match (pc + length).cmp(&a_text) {
-                    cmp::Ordering::Less => {
-                        self.asm
-                            .push(((pc as u16), chunk[..length].to_vec(), instruction));
-                        pc += length;
-                    }
-                    cmp::Ordering::Equal => {
-                        self.asm
-                            .push(((pc as u16), chunk[..length].to_vec(), instruction));
-                        break;
-                    }
-                    cmp::Ordering::Greater => {
-                        self.asm
-                            .push(((pc as u16), [0b00].to_vec(), Instruction::Undefined));
-                        break;
-                    }
+            match Instruction::decode(pc as u16, chunk) {
+                Ok((length, instruction)) => {
+                    self.asm
+                        .push(((pc as u16), chunk[..length].to_vec(), instruction));
+                    pc += length;
                 }
-            } else {
-                panic!("disassembler error, {:?}", chunk);
+                // padding at the end of the text segment
+                Err(MmvmError::TruncatedInput { .. }) => {
+                    self.asm
+                        .push(((pc as u16), [0b00].to_vec(), Instruction::Undefined));
+                    break;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        for symbol in read_symbols(&header, bytes_data)? {
+            if symbol.section() == N_TEXT && !symbol.name.is_empty() {
+                self.labels
+                    .entry(symbol.value as u16)
+                    .or_default()
+                    .push(symbol.name);
             }
         }
+        Ok(())
     }
 
-    pub fn print(&self) {
-        if self.asm.is_empty() {
-            panic!("you haven't done disassembling")
+    // call and jump targets are shown by name when a symbol sits there
+    pub fn render(&self, instruction: &Instruction) -> String {
+        match instruction {
+            &Instruction::WithImmediate(
+                mnemonic,
+                Numerical::Imme(Immediate::UnsignedWord(target)),
+            ) if !matches!(mnemonic, RET | RETF) => match self.labels.get(&target) {
+                Some(names) => format!("{} {} (0x{:04x})", mnemonic, names[0], target),
+                None => format!("{}", instruction),
+            },
+            _ => format!("{}", instruction),
         }
+    }
+
+    // an empty text segment prints nothing
+    pub fn print(&self) {
         for (pc, binary, instruction) in self.asm.clone().into_iter() {
             let binary = binary
                 .iter()
                 .map(|&b| format!("{:02x}", b))
                 .collect::<Vec<String>>()
                 .concat();
-            println!("{:04x}: {}\t{}", pc, binary, instruction);
+            for name in self.labels.get(&pc).into_iter().flatten() {
+                println!("{}:", name);
+            }
+            println!("{:04x}: {}\t{}", pc, binary, self.render(&instruction));
         }
     }
 }
@@ -2,6 +2,8 @@
 mod tests {
     use crate::disassembler::addressing::Addressing;
     use crate::disassembler::instruction::Instruction;
+    use crate::disassembler::Disassembler;
+    use crate::error::MmvmError;
 
     #[test]
     fn test_decode_displacement() {
@@ -534,6 +536,8 @@ mod tests {
         let testcases = [
 	        (0x00e4, &[0xec, 0x00, 0x00, 0x00, 0x00, 0x00], 1, "in al, dx"),
 	        (0x00e2, &[0xe5, 0x83, 0x00, 0x00, 0x00, 0x00], 2, "in ax, 83"),
+	        (0x0000, &[0xe6, 0x40, 0x00, 0x00, 0x00, 0x00], 2, "out 40, al"),
+	        (0x0000, &[0xef, 0x00, 0x00, 0x00, 0x00, 0x00], 1, "out dx, ax"),
         ];
 
         for (i, testcase) in testcases.into_iter().enumerate() {
@@ -1044,6 +1048,9 @@ mod tests {
         let testcases = [
 	        (0x1126, &[0xf2, 0xa4, 0x00, 0x00, 0x00, 0x00], 2, "rep movsb"),
 	        (0x1122, &[0xf2, 0xa5, 0x00, 0x00, 0x00, 0x00], 2, "rep movsw"),
+	        (0x0000, &[0xf3, 0xaa, 0x00, 0x00, 0x00, 0x00], 2, "rep stosb"),
+	        (0x0000, &[0xf3, 0xa6, 0x00, 0x00, 0x00, 0x00], 2, "repe cmpsb"),
+	        (0x0000, &[0xf2, 0xae, 0x00, 0x00, 0x00, 0x00], 2, "repne scasb"),
         ];
 
         for (i, testcase) in testcases.into_iter().enumerate() {
@@ -2944,6 +2951,10 @@ mod tests {
 	        (0x1261, &[0xe8, 0x3b, 0x00, 0x00, 0x00, 0x00], 3, "call 129f"),
 	        (0x00b4, &[0xff, 0xd3, 0x00, 0x00, 0x00, 0x00], 2, "call bx"),
 	        (0x009d, &[0x98, 0x00, 0x00, 0x00, 0x00, 0x00], 1, "cbw"),
+	        (0x0000, &[0x27, 0x00, 0x00, 0x00, 0x00, 0x00], 1, "daa"),
+	        (0x0000, &[0xd4, 0x0a, 0x00, 0x00, 0x00, 0x00], 2, "aam"),
+	        (0x0000, &[0xd4, 0x10, 0x00, 0x00, 0x00, 0x00], 2, "aam 10"),
+	        (0x0000, &[0xd5, 0x07, 0x00, 0x00, 0x00, 0x00], 2, "aad 7"),
 	        (0x1112, &[0xfc, 0x00, 0x00, 0x00, 0x00, 0x00], 1, "cld"),
 	        (0x00c1, &[0x83, 0x3e, 0x06, 0x00, 0x00, 0x00], 5, "cmp [0006], 0"),
 	        (0x0070, &[0x83, 0x3e, 0x0c, 0x00, 0x00, 0x00], 5, "cmp [000c], 0"),
@@ -4777,7 +4788,7 @@ mod tests {
         ];
 
         for (i, testcase) in testcases.into_iter().enumerate() {
-            if let (l, Some(instruction)) = Instruction::decode(testcase.0, testcase.1) {
+            if let Ok((l, instruction)) = Instruction::decode(testcase.0, testcase.1) {
                 assert_eq!(
                     format!("{}", instruction),
                     testcase.3,
@@ -4793,33 +4804,158 @@ mod tests {
                     "#{}, {}, result: {:?}, expected: {}",
                     i, instruction, l, testcase.2
                 );
+            } else {
+                panic!("#{}, result: Err, expected: {}", i, testcase.3);
             }
         }
     }
 
     #[test]
     fn test_decode_undefined() {
-    	let testcases = [
-     		(0x013f, &[0x00], 1, "(undefined)"),
-     	];
-    	for (i, testcase) in testcases.into_iter().enumerate() {
-         	if let (_, Some(instruction)) = Instruction::decode(testcase.0, testcase.1) {
-             	assert_eq!(
-	                 format!("{}", instruction),
-	                 testcase.3,
-	                 "#{}, {:04x}, {:?}, result: {:?}, expected: {}",
-	                 i,
-	                 testcase.0,
-	                 testcase.1,
-	                 instruction,
-	                 testcase.3
-	             );
-	             // assert_eq!(
-	             //     l, testcase.2,
-	             //     "#{}, {}, result: {:?}, expected: {}",
-	             //     i, instruction, l, testcase.2
-	             // );
-	         }
-	     }
+        let testcases = [
+            (
+                0x013f,
+                &[0x00][..],
+                MmvmError::TruncatedInput {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+            (
+                0x0000,
+                &[0x60, 0x00][..],
+                MmvmError::UndecodableOpcode {
+                    address: 0x0000,
+                    bytes: vec![0x60, 0x00],
+                },
+            ),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            assert_eq!(
+                Instruction::decode(testcase.0, testcase.1).err(),
+                Some(testcase.2.clone()),
+                "#{}, {:04x}, {:?}, expected: {}",
+                i,
+                testcase.0,
+                testcase.1,
+                testcase.2
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_symbol() {
+        let mut disassembler = Disassembler::new();
+        disassembler
+            .labels
+            .insert(0x01a4, vec!["_printf".to_string()]);
+        let testcases = [
+            (0x0031, &[0xe8, 0x70, 0x01][..], "call _printf (0x01a4)"),
+            (0x0194, &[0xeb, 0x0e][..], "jmp short _printf (0x01a4)"),
+            (0x0031, &[0xe8, 0x05, 0x00][..], "call 0039"),
+            (0x0031, &[0xc2, 0xa4, 0x01][..], "ret 01a4"),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let (_, instruction) = Instruction::decode(testcase.0, testcase.1).unwrap();
+            assert_eq!(
+                disassembler.render(&instruction),
+                testcase.2,
+                "#{}, expected: {}",
+                i,
+                testcase.2
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_segment_override() {
+        let testcases = [
+            (0x0000, &[0x26, 0x8b, 0x00][..], 3, "mov ax, es:[bx+si]"),
+            (0x0000, &[0x2e, 0xa1, 0x34, 0x12][..], 4, "mov ax, cs:[1234]"),
+            (0x0000, &[0x36, 0x89, 0x47, 0x02][..], 4, "mov ss:[bx+2], ax"),
+            (0x0000, &[0x3e, 0xff, 0x76, 0x04][..], 4, "push ds:[bp+4]"),
+            (
+                0x0000,
+                &[0x26, 0xc7, 0x87, 0x34, 0x12, 0x78, 0x56][..],
+                7,
+                "mov es:[bx+1234], 5678",
+            ),
+            (0x0000, &[0x26, 0xac][..], 2, "es: lodsb"),
+            (0x0000, &[0x26, 0xf3, 0xa4][..], 3, "es: rep movsb"),
+            (0x0000, &[0xf3, 0x26, 0xa4][..], 3, "es: rep movsb"),
+            (0x0000, &[0xf2, 0x2e, 0xa6][..], 3, "cs: repne cmpsb"),
+            (0x0010, &[0x2e, 0xeb, 0x02][..], 3, "cs: jmp short 0015"),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let (l, instruction) = Instruction::decode(testcase.0, testcase.1).unwrap();
+            assert_eq!(
+                (l, format!("{}", instruction)),
+                (testcase.2, testcase.3.to_string()),
+                "#{}, expected: {}",
+                i,
+                testcase.3
+            );
+        }
+        assert_eq!(
+            Instruction::decode(0x0000, &[0x26, 0x8b]).err(),
+            Some(MmvmError::TruncatedInput {
+                expected: 3,
+                actual: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_far_transfer() {
+        let testcases = [
+            (&[0x9a, 0x78, 0x56, 0x34, 0x12][..], 5, "call far 1234:5678"),
+            (&[0xea, 0x00, 0x00, 0xff, 0xff][..], 5, "jmp far ffff:0000"),
+            (&[0xff, 0x1f][..], 2, "call far [bx]"),
+            (&[0xff, 0x6e, 0x04][..], 3, "jmp far [bp+4]"),
+            (&[0xcb][..], 1, "retf"),
+            (&[0xca, 0x04, 0x00][..], 3, "retf 0004"),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let (l, instruction) = Instruction::decode(0x0000, testcase.0).unwrap();
+            assert_eq!(
+                (l, format!("{}", instruction)),
+                (testcase.1, testcase.2.to_string()),
+                "#{}, expected: {}",
+                i,
+                testcase.2
+            );
+        }
+        // there is no far pointer in a register
+        assert!(Instruction::decode(0x0000, &[0xff, 0xdb]).is_err());
+    }
+
+    #[test]
+    fn test_decode_with_size() {
+        let testcases = [
+            (&[0x24, 0x0f][..], 2, "and al, f", false),
+            (&[0x25, 0x0f, 0x00][..], 3, "and ax, 000f", true),
+            (&[0x83, 0xe0, 0xfe][..], 3, "and ax, -2", true),
+            (&[0xf6, 0xe3][..], 2, "mul bl", false),
+            (&[0xf6, 0xd0][..], 2, "not al", false),
+            (&[0xf7, 0x17][..], 2, "not [bx]", true),
+            (&[0xfe, 0x07][..], 2, "inc [bx]", false),
+            (&[0xff, 0x07][..], 2, "inc [bx]", true),
+            (&[0x26, 0xfe, 0x07][..], 3, "inc es:[bx]", false),
+            (&[0x8e, 0x07][..], 2, "mov es, [bx]", true),
+            (&[0xb8, 0x34, 0x12][..], 3, "mov ax, 1234", true),
+            (&[0xb0, 0x68][..], 2, "mov al, 68", false),
+            (&[0xb7, 0xff][..], 2, "mov bh, ff", false),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let (l, instruction, word_mode) =
+                Instruction::decode_with_size(0x0000, testcase.0).unwrap();
+            assert_eq!(
+                (l, format!("{}", instruction), word_mode),
+                (testcase.1, testcase.2.to_string(), testcase.3),
+                "#{}, expected: {}",
+                i,
+                testcase.2
+            );
+        }
     }
 }
@@ -0,0 +1,107 @@
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MmvmError {
+    // no input file was given on the command line
+    MissingInput,
+    Io { path: PathBuf, kind: ErrorKind },
+    // the input ended before a whole header or instruction could be read
+    TruncatedInput { expected: usize, actual: usize },
+    BadMagic(Vec<u8>),
+    UnsupportedCpu(u8),
+    BadHeaderLength(u8),
+    UndecodableOpcode { address: u16, bytes: Vec<u8> },
+    UnimplementedInstruction { address: u16, bytes: Vec<u8> },
+    MemoryFault { address: u16, bytes: Vec<u8> },
+    // interrupt type 0, raised by DIV / IDIV on a zero divisor or an oversized quotient
+    DivideError { address: u16, bytes: Vec<u8> },
+    InvalidSyscall { address: u16, call: u16 },
+    // INT to a vector with neither a host handler nor an IVT entry
+    UnhandledInterrupt { address: u16, vector: u8 },
+}
+
+impl MmvmError {
+    // the execution stage only sees the fetched bytes, the interpreter knows where they came from
+    pub fn at(self, ip: u16, binary_data: &[u8]) -> Self {
+        match self {
+            Self::UndecodableOpcode { .. } => Self::UndecodableOpcode {
+                address: ip,
+                bytes: binary_data.to_vec(),
+            },
+            Self::UnimplementedInstruction { .. } => Self::UnimplementedInstruction {
+                address: ip,
+                bytes: binary_data.to_vec(),
+            },
+            Self::MemoryFault { .. } => Self::MemoryFault {
+                address: ip,
+                bytes: binary_data.to_vec(),
+            },
+            Self::DivideError { .. } => Self::DivideError {
+                address: ip,
+                bytes: binary_data.to_vec(),
+            },
+            Self::InvalidSyscall { call, .. } => Self::InvalidSyscall { address: ip, call },
+            Self::UnhandledInterrupt { vector, .. } => Self::UnhandledInterrupt {
+                address: ip,
+                vector,
+            },
+            error => error,
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+impl Display for MmvmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingInput => write!(f, "no input file, pass one with -d, -m or --header"),
+            Self::Io { path, kind } => write!(f, "cannot read {}: {}", path.display(), kind),
+            Self::TruncatedInput { expected, actual } => write!(
+                f,
+                "truncated input, expected {} bytes but only {} left",
+                expected, actual
+            ),
+            Self::BadMagic(magic) => write!(f, "bad header magic {}, expected 0103", hex(magic)),
+            Self::UnsupportedCpu(a_cpu) => {
+                write!(f, "unsupported cpu 0x{:02x}, expected 0x04 (8086)", a_cpu)
+            }
+            Self::BadHeaderLength(a_hdrlen) => {
+                write!(f, "bad header length {}, expected 32 or 48", a_hdrlen)
+            }
+            Self::UndecodableOpcode { address, bytes } => {
+                write!(f, "undecodable opcode at {:04x}: {}", address, hex(bytes))
+            }
+            Self::UnimplementedInstruction { address, bytes } => {
+                write!(
+                    f,
+                    "unimplemented instruction at {:04x}: {}",
+                    address,
+                    hex(bytes)
+                )
+            }
+            Self::MemoryFault { address, bytes } => {
+                write!(f, "memory fault at {:04x}: {}", address, hex(bytes))
+            }
+            Self::DivideError { address, bytes } => {
+                write!(f, "divide error at {:04x}: {}", address, hex(bytes))
+            }
+            Self::InvalidSyscall { address, call } => {
+                write!(f, "invalid system call {} at {:04x}", call, address)
+            }
+            Self::UnhandledInterrupt { address, vector } => {
+                write!(f, "unhandled interrupt 0x{:02x} at {:04x}", vector, address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MmvmError {}
@@ -0,0 +1,474 @@
+use crate::disassembler::mnemonic::Mnemonic;
+use crate::disassembler::mnemonic::Mnemonic::*;
+use crate::disassembler::numerical::Immediate;
+use crate::interpreter::hardware::{Flag, Hardware};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Add,
+    Or,
+    Adc,
+    Sbb,
+    And,
+    Sub,
+    Xor,
+    Cmp,
+    Test,
+    Inc,
+    Dec,
+    Neg,
+    Shl,
+    Shr,
+    Sar,
+    Rol,
+    Ror,
+    Rcl,
+    Rcr,
+}
+
+impl Operation {
+    // the operation behind an arithmetic, logic, shift or rotate mnemonic
+    pub fn from_mnemonic(mnemonic: Mnemonic) -> Option<Self> {
+        match mnemonic {
+            ADD => Some(Operation::Add),
+            OR => Some(Operation::Or),
+            ADC => Some(Operation::Adc),
+            SSB => Some(Operation::Sbb),
+            AND => Some(Operation::And),
+            SUB => Some(Operation::Sub),
+            XOR => Some(Operation::Xor),
+            CMP | CMPBYTE => Some(Operation::Cmp),
+            TEST | TESTBYTE => Some(Operation::Test),
+            INC => Some(Operation::Inc),
+            DEC => Some(Operation::Dec),
+            NEG => Some(Operation::Neg),
+            SHL => Some(Operation::Shl),
+            SHR => Some(Operation::Shr),
+            SAR => Some(Operation::Sar),
+            ROL => Some(Operation::Rol),
+            ROR => Some(Operation::Ror),
+            RCL => Some(Operation::Rcl),
+            RCR => Some(Operation::Rcr),
+            _ => None,
+        }
+    }
+
+    // CMP and TEST only leave their mark on the flags
+    pub fn writes_back(&self) -> bool {
+        !matches!(self, Operation::Cmp | Operation::Test)
+    }
+}
+
+// the six status flags an ALU operation produces, everything else in FLAGS is control
+const STATUS_MASK: u16 = Flag::CF.mask()
+    | Flag::PF.mask()
+    | Flag::AF.mask()
+    | Flag::ZF.mask()
+    | Flag::SF.mask()
+    | Flag::OF.mask();
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Flags {
+    pub cf: bool,
+    pub pf: bool,
+    pub af: bool,
+    pub zf: bool,
+    pub sf: bool,
+    pub of: bool,
+}
+
+impl From<u16> for Flags {
+    fn from(flag: u16) -> Self {
+        Self {
+            cf: (flag & Flag::CF.mask()) != 0,
+            pf: (flag & Flag::PF.mask()) != 0,
+            af: (flag & Flag::AF.mask()) != 0,
+            zf: (flag & Flag::ZF.mask()) != 0,
+            sf: (flag & Flag::SF.mask()) != 0,
+            of: (flag & Flag::OF.mask()) != 0,
+        }
+    }
+}
+
+impl Flags {
+    // replace the status bits of a flag register, control bits (TF, IF, DF) are kept
+    pub fn merge(&self, flag: u16) -> u16 {
+        (flag & !STATUS_MASK)
+            | (u16::from(self.cf) * Flag::CF.mask())
+            | (u16::from(self.pf) * Flag::PF.mask())
+            | (u16::from(self.af) * Flag::AF.mask())
+            | (u16::from(self.zf) * Flag::ZF.mask())
+            | (u16::from(self.sf) * Flag::SF.mask())
+            | (u16::from(self.of) * Flag::OF.mask())
+    }
+}
+
+fn parity(result: u32) -> bool {
+    (result & 0xff).count_ones() % 2 == 0
+}
+
+// Compute operation on operands of one size and return the result with the six status flags,
+// flags the 8086 leaves alone for an operation are carried over from flags
+pub fn calculate(
+    operation: Operation,
+    word_mode: bool,
+    destination: u16,
+    source: u16,
+    flags: Flags,
+) -> (u16, Flags) {
+    let (mask, sign) = if word_mode {
+        (0xffffu32, 0x8000u32)
+    } else {
+        (0x00ffu32, 0x0080u32)
+    };
+    let (a, b) = (u32::from(destination) & mask, u32::from(source) & mask);
+    let mut output = flags;
+
+    let result = match operation {
+        Operation::Add | Operation::Adc | Operation::Inc => {
+            let (b, carry) = match operation {
+                Operation::Inc => (1, 0),
+                Operation::Adc => (b, u32::from(flags.cf)),
+                _ => (b, 0),
+            };
+            let result = a + b + carry;
+            if operation != Operation::Inc {
+                output.cf = result > mask;
+            }
+            output.of = ((a ^ result) & (b ^ result) & sign) != 0;
+            output.af = ((a ^ b ^ result) & 0x10) != 0;
+            result & mask
+        }
+        Operation::Sub | Operation::Sbb | Operation::Cmp | Operation::Dec | Operation::Neg => {
+            let (a, b, borrow) = match operation {
+                Operation::Dec => (a, 1, 0),
+                Operation::Neg => (0, a, 0),
+                Operation::Sbb => (a, b, u32::from(flags.cf)),
+                _ => (a, b, 0),
+            };
+            let result = a.wrapping_sub(b).wrapping_sub(borrow);
+            if operation != Operation::Dec {
+                output.cf = a < b + borrow;
+            }
+            output.of = ((a ^ b) & (a ^ result) & sign) != 0;
+            output.af = ((a ^ b ^ result) & 0x10) != 0;
+            result & mask
+        }
+        Operation::And | Operation::Or | Operation::Xor | Operation::Test => {
+            output.cf = false;
+            output.of = false;
+            output.af = false;
+            match operation {
+                Operation::Or => a | b,
+                Operation::Xor => a ^ b,
+                _ => a & b,
+            }
+        }
+        // shifts take their count from source, a count of 0 changes nothing
+        Operation::Shl
+        | Operation::Shr
+        | Operation::Sar
+        | Operation::Rol
+        | Operation::Ror
+        | Operation::Rcl
+        | Operation::Rcr => {
+            if b == 0 {
+                return (destination, flags);
+            }
+            let mut result = a;
+            let mut carry = flags.cf;
+            for _ in 0..b {
+                match operation {
+                    Operation::Shl => {
+                        carry = (result & sign) != 0;
+                        result = (result << 1) & mask;
+                    }
+                    Operation::Shr => {
+                        carry = (result & 0b1) != 0;
+                        result >>= 1;
+                    }
+                    Operation::Sar => {
+                        carry = (result & 0b1) != 0;
+                        result = (result >> 1) | (result & sign);
+                    }
+                    Operation::Rol => {
+                        carry = (result & sign) != 0;
+                        result = ((result << 1) | u32::from(carry)) & mask;
+                    }
+                    Operation::Ror => {
+                        carry = (result & 0b1) != 0;
+                        result = (result >> 1) | if carry { sign } else { 0 };
+                    }
+                    Operation::Rcl => {
+                        let msb = (result & sign) != 0;
+                        result = ((result << 1) | u32::from(carry)) & mask;
+                        carry = msb;
+                    }
+                    _ => {
+                        let lsb = (result & 0b1) != 0;
+                        result = (result >> 1) | if carry { sign } else { 0 };
+                        carry = lsb;
+                    }
+                }
+            }
+            output.cf = carry;
+            match operation {
+                Operation::Shl | Operation::Shr | Operation::Sar => {
+                    output.of = match operation {
+                        Operation::Shr => (a & sign) != 0,
+                        Operation::Sar => false,
+                        _ => ((result & sign) != 0) != carry,
+                    };
+                    result
+                }
+                // rotates only touch CF and OF, and OF only has a meaning for a count of 1
+                _ => {
+                    if b == 1 {
+                        output.of = match operation {
+                            Operation::Rol | Operation::Rcl => ((result & sign) != 0) != carry,
+                            _ => ((result ^ (result << 1)) & sign) != 0,
+                        };
+                    }
+                    return (result as u16, output);
+                }
+            }
+        }
+    };
+    output.zf = result == 0;
+    output.sf = (result & sign) != 0;
+    output.pf = parity(result);
+    (result as u16, output)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Adjustment {
+    Aaa,
+    Daa,
+    Aas,
+    Das,
+    Aam,
+    Aad,
+}
+
+// Apply a BCD / ASCII adjustment to AX, base is the immediate byte of AAM and AAD.
+// None means AAM was asked to divide by 0
+pub fn adjust(adjustment: Adjustment, ax: u16, base: u8, flags: Flags) -> Option<(u16, Flags)> {
+    let [al, ah] = ax.to_le_bytes();
+    let mut output = flags;
+    let low_digit = (al & 0x0f) > 9 || flags.af;
+    let (al, ah) = match adjustment {
+        // unpacked: carry a decimal digit into AH and keep only the low nibble in AL
+        Adjustment::Aaa | Adjustment::Aas => {
+            output.af = low_digit;
+            output.cf = low_digit;
+            let (al, ah) = match (low_digit, adjustment) {
+                (false, _) => (al, ah),
+                (true, Adjustment::Aaa) => (al.wrapping_add(6), ah.wrapping_add(1)),
+                (true, _) => (al.wrapping_sub(6), ah.wrapping_sub(1)),
+            };
+            return Some((u16::from_le_bytes([al & 0x0f, ah]), output));
+        }
+        // packed: fix up each nibble of AL in turn
+        Adjustment::Daa | Adjustment::Das => {
+            let high_digit = al > 0x99 || flags.cf;
+            let mut result = al;
+            output.af = low_digit;
+            output.cf = false;
+            if low_digit {
+                let (adjusted, carry) = if adjustment == Adjustment::Daa {
+                    result.overflowing_add(0x06)
+                } else {
+                    result.overflowing_sub(0x06)
+                };
+                result = adjusted;
+                output.cf = flags.cf || carry;
+            }
+            if high_digit {
+                result = if adjustment == Adjustment::Daa {
+                    result.wrapping_add(0x60)
+                } else {
+                    result.wrapping_sub(0x60)
+                };
+                output.cf = true;
+            }
+            (result, ah)
+        }
+        Adjustment::Aam => {
+            if base == 0 {
+                return None;
+            }
+            (al % base, al / base)
+        }
+        Adjustment::Aad => (ah.wrapping_mul(base).wrapping_add(al), 0x00),
+    };
+    output.zf = al == 0;
+    output.sf = (al & 0x80) != 0;
+    output.pf = parity(u32::from(al));
+    Some((u16::from_le_bytes([al, ah]), output))
+}
+
+pub fn operand(immediate: &Immediate) -> u16 {
+    match immediate {
+        &Immediate::UnsignedWord(immediate) => immediate,
+        &Immediate::SignedWord(immediate) => immediate as u16,
+        &Immediate::UnsignedByte(immediate) => u16::from(immediate),
+        &Immediate::SignedByte(immediate) => u16::from(immediate as u8),
+    }
+}
+
+// Run operation on a register or memory value and update the flags of hardware,
+// the operand size follows destination
+pub fn operate(
+    operation: Operation,
+    destination: &Immediate,
+    source: u16,
+    hardware: &mut Hardware,
+) -> Immediate {
+    let word_mode = matches!(
+        destination,
+        Immediate::UnsignedWord(_) | Immediate::SignedWord(_)
+    );
+    let flag = hardware.read_flag_register();
+    let (result, flags) = calculate(
+        operation,
+        word_mode,
+        operand(destination),
+        source,
+        Flags::from(flag),
+    );
+    hardware.write_flag_register(flags.merge(flag));
+    if word_mode {
+        Immediate::UnsignedWord(result)
+    } else {
+        Immediate::UnsignedByte(result as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::alu::{adjust, calculate, Adjustment, Flags, Operation, STATUS_MASK};
+
+    fn flags(string: &str) -> Flags {
+        Flags {
+            cf: string.contains('C'),
+            pf: string.contains('P'),
+            af: string.contains('A'),
+            zf: string.contains('Z'),
+            sf: string.contains('S'),
+            of: string.contains('O'),
+        }
+    }
+
+    #[test]
+    fn test_calculate() {
+        let testcases = [
+            (Operation::Add, false, 0x7f, 0x01, "", 0x80, "ASO"),
+            (Operation::Add, false, 0xff, 0x01, "", 0x00, "CPAZ"),
+            (Operation::Add, true, 0xffff, 0x0001, "", 0x0000, "CPAZ"),
+            (Operation::Adc, true, 0x7fff, 0x0000, "C", 0x8000, "PASO"),
+            (Operation::Adc, false, 0x01, 0x01, "", 0x02, ""),
+            (Operation::Sub, false, 0x00, 0x01, "", 0xff, "CPAS"),
+            (Operation::Sub, true, 0x8000, 0x0001, "", 0x7fff, "PAO"),
+            (Operation::Sbb, false, 0x05, 0x05, "C", 0xff, "CPAS"),
+            (Operation::Cmp, true, 0x0014, 0x0014, "", 0x0000, "PZ"),
+            (Operation::Neg, false, 0x80, 0x00, "", 0x80, "CSO"),
+            (Operation::Neg, true, 0x0000, 0x0000, "C", 0x0000, "PZ"),
+            (Operation::Neg, true, 0x0001, 0x0000, "", 0xffff, "CPAS"),
+            (Operation::Inc, false, 0x7f, 0x00, "C", 0x80, "CASO"),
+            (Operation::Dec, true, 0x0000, 0x0000, "", 0xffff, "PAS"),
+            (Operation::And, false, 0xf0, 0x0f, "CO", 0x00, "PZ"),
+            (Operation::Xor, true, 0x8001, 0x0000, "", 0x8001, "S"),
+            (Operation::Or, false, 0x03, 0x00, "", 0x03, "P"),
+            (Operation::Shl, false, 0x81, 0x01, "", 0x02, "CO"),
+            (Operation::Shl, true, 0x4000, 0x01, "", 0x8000, "PSO"),
+            (Operation::Shl, false, 0x81, 0x00, "A", 0x81, "A"),
+            (Operation::Shr, false, 0x81, 0x01, "", 0x40, "CO"),
+            (Operation::Shr, true, 0x0004, 0x03, "C", 0x0000, "CPZ"),
+            (Operation::Sar, false, 0x81, 0x01, "O", 0xc0, "CPS"),
+            (Operation::Rcl, false, 0x80, 0x01, "Z", 0x00, "CZO"),
+            (Operation::Rcl, true, 0x0001, 0x02, "C", 0x0006, ""),
+            (Operation::Rcl, true, 0x8000, 0x02, "O", 0x0001, "O"),
+            (Operation::Rol, false, 0x81, 0x01, "Z", 0x03, "CZO"),
+            (Operation::Rol, false, 0x41, 0x01, "", 0x82, "O"),
+            (Operation::Rol, true, 0x1234, 0x04, "", 0x2341, "C"),
+            (Operation::Rol, false, 0x81, 0x00, "O", 0x81, "O"),
+            (Operation::Ror, false, 0x01, 0x01, "", 0x80, "CO"),
+            (Operation::Ror, true, 0x0002, 0x01, "C", 0x0001, ""),
+            (Operation::Ror, true, 0x1234, 0x04, "", 0x4123, ""),
+            (Operation::Rcr, false, 0x01, 0x01, "", 0x00, "C"),
+            (Operation::Rcr, false, 0x00, 0x01, "C", 0x80, "O"),
+            (Operation::Rcr, true, 0x0001, 0x11, "", 0x0001, ""),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let (result, output) = calculate(
+                testcase.0,
+                testcase.1,
+                testcase.2,
+                testcase.3,
+                flags(testcase.4),
+            );
+            assert_eq!(
+                (result, output),
+                (testcase.5, flags(testcase.6)),
+                "#{}, {:?} {:04x}, {:04x}, expected: {:04x} {}",
+                i,
+                testcase.0,
+                testcase.2,
+                testcase.3,
+                testcase.5,
+                testcase.6
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_flags() {
+        let flag = 0x0400 | 0x0001;
+        assert_eq!(flags("ZS").merge(flag), 0x0400 | 0x00c0);
+        assert_eq!(STATUS_MASK, 0x08d5);
+        assert_eq!(Flags::from(STATUS_MASK), flags("CPAZSO"));
+    }
+
+    #[test]
+    fn test_adjust() {
+        let testcases = [
+            // 0x38 + 0x45 = 0x7d, decimal 83
+            (Adjustment::Daa, 0x007d, 0x0a, "", 0x0083, "AS"),
+            // 0x99 + 0x01 = 0x9a, decimal 00 carry 1
+            (Adjustment::Daa, 0x009a, 0x0a, "", 0x0000, "CPAZ"),
+            (Adjustment::Daa, 0x0012, 0x0a, "A", 0x0018, "PA"),
+            // 0x10 - 0x01 = 0x0f, decimal 09
+            (Adjustment::Das, 0x000f, 0x0a, "A", 0x0009, "PA"),
+            // 0x00 - 0x01 = 0xff, decimal 99 borrow 1
+            (Adjustment::Das, 0x00ff, 0x0a, "CA", 0x0099, "CPAS"),
+            // '9' + '5' = 0x6e, unpacked 0104
+            (Adjustment::Aaa, 0x006e, 0x0a, "", 0x0104, "CA"),
+            // '8' + '9' = 0x71 with AF
+            (Adjustment::Aaa, 0x0071, 0x0a, "A", 0x0107, "CA"),
+            (Adjustment::Aaa, 0x0005, 0x0a, "CZ", 0x0005, "Z"),
+            // 0x02 - 0x05 = 0xfd with AF, unpacked borrow
+            (Adjustment::Aas, 0x01fd, 0x0a, "A", 0x0007, "CA"),
+            (Adjustment::Aam, 0x003f, 0x0a, "", 0x0603, "P"),
+            (Adjustment::Aam, 0x00ff, 0x10, "", 0x0f0f, "P"),
+            (Adjustment::Aad, 0x0603, 0x0a, "", 0x003f, "P"),
+            (Adjustment::Aad, 0x0f0f, 0x10, "", 0x00ff, "PS"),
+            (Adjustment::Aad, 0x0000, 0x07, "", 0x0000, "PZ"),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            assert_eq!(
+                adjust(testcase.0, testcase.1, testcase.2, flags(testcase.3)),
+                Some((testcase.4, flags(testcase.5))),
+                "#{}, {:?} {:04x}, expected: {:04x} {}",
+                i,
+                testcase.0,
+                testcase.1,
+                testcase.4,
+                testcase.5
+            );
+        }
+        assert_eq!(
+            adjust(Adjustment::Aam, 0x0010, 0x00, Flags::default()),
+            None
+        );
+    }
+}
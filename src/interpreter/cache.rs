@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::disassembler::instruction::Instruction;
+use crate::interpreter::hardware::MEMORY_SIZE;
+
+// an instruction never spans more than the chunk fetched for it
+pub const MAX_LENGTH: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    // relative targets are decoded against IP, the same bytes reached from another CS:IP differ
+    pub ip: u16,
+    pub chunk: [u8; MAX_LENGTH],
+    pub length: usize,
+    pub instruction: Instruction,
+    pub word_mode: bool,
+}
+
+// Decoded instructions keyed by the physical address of their first byte
+#[derive(Debug, Clone)]
+pub struct DecodeCache {
+    entries: HashMap<u32, Decoded>,
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, addr: u32, ip: u16) -> Option<&Decoded> {
+        self.entries.get(&addr).filter(|decoded| decoded.ip == ip)
+    }
+
+    pub fn insert(&mut self, addr: u32, decoded: Decoded) {
+        self.entries.insert(addr, decoded);
+    }
+
+    // drop every entry whose bytes cover addr, the instruction may start up to 7 bytes before it
+    pub fn invalidate(&mut self, addr: u32) {
+        for back in 0..MAX_LENGTH as u32 {
+            let start = addr.wrapping_sub(back) & (MEMORY_SIZE as u32 - 1);
+            if self
+                .entries
+                .get(&start)
+                .is_some_and(|decoded| decoded.length as u32 > back)
+            {
+                self.entries.remove(&start);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::instruction::Instruction;
+    use crate::interpreter::cache::{DecodeCache, Decoded};
+
+    fn decoded(ip: u16, chunk: [u8; 8]) -> Decoded {
+        let (length, instruction, word_mode) = Instruction::decode_with_size(ip, &chunk).unwrap();
+        Decoded {
+            ip,
+            chunk,
+            length,
+            instruction,
+            word_mode,
+        }
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut cache = DecodeCache::new();
+        // mov ax, 1234 at 10000 and inc ax at 10003
+        cache.insert(0x10000, decoded(0x0000, [0xb8, 0x34, 0x12, 0, 0, 0, 0, 0]));
+        cache.insert(0x10003, decoded(0x0003, [0x40, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(cache.get(0x10000, 0x0000).unwrap().length, 3);
+        assert!(cache.get(0x10000, 0x0100).is_none());
+
+        // a write past the end of an instruction leaves it alone
+        cache.invalidate(0x10004);
+        assert!(cache.get(0x10000, 0x0000).is_some());
+        assert!(cache.get(0x10003, 0x0003).is_some());
+        cache.invalidate(0x10002);
+        assert!(cache.get(0x10000, 0x0000).is_none());
+        assert!(cache.get(0x10003, 0x0003).is_some());
+        cache.invalidate(0x10003);
+        assert!(cache.get(0x10003, 0x0003).is_none());
+    }
+}
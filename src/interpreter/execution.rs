@@ -1,1465 +1,1311 @@
 use crate::disassembler::addressing::Addressing;
 use crate::disassembler::direction::Direction;
+use crate::disassembler::instruction::Instruction;
+use crate::disassembler::mnemonic::Mnemonic;
+use crate::disassembler::mnemonic::Mnemonic::*;
 use crate::disassembler::numerical::{Immediate, Numerical};
 use crate::disassembler::register::ByteRegister::{AH, AL, CL};
 use crate::disassembler::register::Register;
+use crate::disassembler::register::SegmentRegister::{DS, ES};
 use crate::disassembler::register::WordRegister::{AX, DX};
 
-use crate::interpreter::{hardware::Hardware, utils::*};
+use crate::interpreter::alu::{adjust, operand, operate, Adjustment, Flags, Operation};
+use crate::interpreter::hardware::{Flag, Hardware, FLAG_MASK, FLAG_RESERVED};
+use crate::interpreter::utils::*;
 
-use crate::interpreter::systemcall::execute_systemcall;
+use crate::interpreter::interrupt::{Interrupts, BREAKPOINT, OVERFLOW};
+use crate::interpreter::port::PortBus;
+use crate::interpreter::systemcall::Minix;
 
-fn match_reg(binary_data: u8, reference: &[u8]) -> bool {
-    let reg = (binary_data & 0b00111000) >> 3;
-    reference.contains(&reg)
-}
+use crate::error::MmvmError;
 
-pub fn decode_data(
-    word_mode: bool,
-    sign_enable: bool,
-    binary_data: &[u8],
-) -> (usize, Option<Immediate>) {
-    if binary_data.is_empty() {
-        return (0, None);
-    }
-    if !word_mode {
-        (1, Some(Immediate::from(&binary_data[0..1], sign_enable)))
-    } else {
-        (2, Some(Immediate::from(&binary_data[0..2], sign_enable)))
+// registers carry their own size, a memory operand takes the w bit of the opcode
+fn operand_size(address: &Addressing, word_mode: bool) -> bool {
+    match address {
+        Addressing::RegisterAddressing(Register::ByteReg(_)) => false,
+        Addressing::RegisterAddressing(_) => true,
+        _ => word_mode,
     }
 }
 
-pub fn execute_move_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // Register/Memory to/from Register
-        0b10001000..=0b10001011 => {
-            let d = (binary_data[0] & 0b00000010) >> 1;
-            let w = binary_data[0] & 0b00000001;
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11111111)
-            {
-                write_from_address_to_address(Direction::from(d), &reg, &r_m, hardware)
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Immediate to Register/Memory
-        0b11000110 | 0b11000111 if match_reg(binary_data[1], &[0b000]) => {
-            let w = binary_data[0] & 0b00000001;
-            if let (rl, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-                if let (_, Some(immediate)) = decode_data(w == 0b1, false, &binary_data[(2 + rl)..])
-                {
-                    write_to_address(&r_m, &immediate, hardware)
-                } else {
-                    panic!("Instruction decode error")
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Immediate to Register
-        0b10110000..=0b10111111 => {
-            let w = (binary_data[0] & 0b00001000) >> 3;
-            if let (_, Some(reg), None) = Addressing::decode(w, binary_data, 0b00000111) {
-                if let (_, Some(immediate)) = decode_data(w == 0b1, false, &binary_data[1..]) {
-                    write_to_address(&reg, &immediate, hardware)
-                } else {
-                    panic!("Instruction decode error")
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Memory <-> Accumulator
-        0b10100000 | 0b10100001 | 0b10100010 | 0b10100011 => {
-            let d = (binary_data[0] & 0b00000010) >> 1;
-            let w = binary_data[0] & 0b00000001;
-            if let (_, Some(address)) = decode_data(true, false, &binary_data[1..]) {
-                write_from_address_to_address(
-                    Direction::from(d),
-                    &Addressing::RegisterAddressing(
-                        Register::decode(w == 0b1, true, 0b000).unwrap(),
-                    ),
-                    &Addressing::DirectAddressing(Numerical::Imme(address)),
-                    hardware,
-                )
-            } else {
-                panic!("Instruction decode error")
-            }
+pub fn execute_move_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    match instruction {
+        // Register/Memory to/from Register, Memory <-> Accumulator, Register/Memory <-> Segment Register
+        &Instruction::AddressToAddress(_, direction, reg, r_m) => {
+            write_from_address_to_address(direction, &reg, &r_m, hardware)?
         }
-        // Register/Memory <-> Segment Register
-        0b10001110 | 0b10001100 if (binary_data[1] & 0b00100000) == 0b00000000 => {
-            let d = (binary_data[0] & 0b00000010) >> 1;
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(0, &binary_data[1..], 0b11011111)
-            {
-                write_from_address_to_address(Direction::from(d), &reg, &r_m, hardware)
-            } else {
-                panic!("Instruction decode error")
-            }
+        // Immediate to Register/Memory, Immediate to Register
+        Instruction::ImmediateToAddress(_, address, Numerical::Imme(immediate)) => {
+            write_to_address(address, immediate, hardware)?
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_push_pop_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // PUSH (Register/Memory)
-        0b11111111 if (binary_data[1] & 0b00111000) == 0b00110000 => {
-            if let (_, None, Some(r_m)) = Addressing::decode(0, &binary_data[1..], 0b11000111) {
-                if let Some(Immediate::UnsignedWord(imme)) = read_from_address(true, &r_m, hardware)
-                {
-                    hardware.push_to_stack(imme)
-                } else {
-                    unreachable!()
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // PUSH (Register)
-        0b01010000..=0b01010111 => {
-            if let (_, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
-                if let Some(Immediate::UnsignedWord(imme)) = read_from_address(true, &reg, hardware)
-                {
-                    hardware.push_to_stack(imme)
-                } else {
-                    unreachable!()
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // POP (Register/Memory)
-        0b10001111 if (binary_data[1] & 0b00111000) == 0b00000000 => {
-            if let (_, None, Some(r_m)) = Addressing::decode(0, &binary_data[1..], 0b11000111) {
-                if let Some(imme) = hardware.pop_from_stack() {
-                    write_to_address(&r_m, &Immediate::UnsignedWord(imme), hardware)
-                } else {
-                    panic!("Not enough data")
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
+pub fn execute_push_pop_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    match instruction {
+        // PUSH (Register/Memory, Register, Segment Register)
+        Instruction::WithAddress(PUSH, address) => {
+            let imme = read_from_address(true, address, hardware).ok_or_else(memory_fault)?;
+            hardware.push_to_stack(operand(&imme))
         }
-        // POP (Register)
-        0b01011000..=0b01011111 => {
-            if let (_, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
-                if let Some(imme) = hardware.pop_from_stack() {
-                    write_to_address(&reg, &Immediate::UnsignedWord(imme), hardware)
-                } else {
-                    panic!("Not enough data")
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // PUSH / POP (Segment Register)
-        _ => {
-            if let (_, Some(reg), None) = Addressing::decode(0, &binary_data[1..], 0b00011000) {
-                if (binary_data[0] & 0b11100111) == 0b00000110 {
-                    if let Some(Immediate::UnsignedWord(imme)) =
-                        read_from_address(true, &reg, hardware)
-                    {
-                        hardware.push_to_stack(imme);
-                    } else {
-                        unreachable!()
-                    }
-                } else if (binary_data[0] & 0b11100111) == 0b00000111 {
-                    if let Some(imme) = hardware.pop_from_stack() {
-                        write_to_address(&reg, &Immediate::UnsignedWord(imme), hardware)
-                    } else {
-                        panic!("Not enough data")
-                    }
-                } else {
-                    panic!("Instruction decode error");
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
+        // POP (Register/Memory, Register, Segment Register)
+        Instruction::WithAddress(POP, address) => {
+            let imme = hardware.pop_from_stack();
+            write_to_address(address, &Immediate::UnsignedWord(imme), hardware)?
         }
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_exchange_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // Register/Memory with Register
-        0b10000110 | 0b10000111 => {
-            let w = binary_data[0] & 0b00000001;
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11111111)
-            {
-                let imme =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrive data");
-                write_from_address_to_address(Direction::FromReg, &reg, &r_m, hardware);
-                write_to_address(&reg, &imme, hardware);
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Register with Accumulator
-        0b10010000..=0b10010111 => {
-            if let (_, Some(reg), None) = Addressing::decode(0, binary_data, 0b00000111) {
-                let imme = read_from_address(true, &reg, hardware).expect("Unable to retrive data");
-                let accumulator = Addressing::RegisterAddressing(Register::WordReg(AX));
-                write_from_address_to_address(Direction::FromReg, &accumulator, &reg, hardware);
-                write_to_address(&reg, &imme, hardware);
-            } else {
-                panic!("Instruction decode error")
-            }
+pub fn execute_in_out_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+    ports: &mut PortBus,
+) -> Result<(), MmvmError> {
+    let (mnemonic, port, accumulator) = match instruction {
+        // Fixed Port
+        &Instruction::ImmediateToAddress(
+            mnemonic,
+            accumulator,
+            Numerical::Imme(Immediate::UnsignedByte(port)),
+        ) => (mnemonic, u16::from(port), accumulator),
+        // Variable Port
+        &Instruction::AddressToAddress(mnemonic, _, _, accumulator) => {
+            (mnemonic, hardware.dx, accumulator)
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
+    };
+    match (mnemonic, operand_size(&accumulator, true)) {
+        (IN, false) => hardware.write_to_byte_register(AL, ports.read_byte(port)),
+        (IN, true) => hardware.write_to_word_register(AX, ports.read_word(port)),
+        (OUT, false) => ports.write_byte(port, hardware.read_from_byte_register(AL)),
+        (OUT, true) => ports.write_word(port, hardware.ax),
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_load_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match (
-        Addressing::decode(0b1, &binary_data[1..], 0b11111111),
-        binary_data[0],
-    ) {
-        ((_, Some(reg), Some(r_m)), 0b10001101) => {
-            write_from_address_to_address(Direction::ToReg, &reg, &r_m, hardware)
-        }
-        ((_, Some(_), Some(_)), 0b11000101) => todo!("LDS"),
-        ((_, Some(_), Some(_)), 0b11000100) => todo!("LES"),
-        _ => panic!("Instruction decode error"),
+pub fn execute_exchange_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    // Register/Memory with Register, Register with Accumulator
+    if let Instruction::AddressToAddress(_, _, reg, r_m) = instruction {
+        let imme =
+            read_from_address(operand_size(reg, true), r_m, hardware).ok_or_else(memory_fault)?;
+        write_from_address_to_address(Direction::FromReg, reg, r_m, hardware)?;
+        write_to_address(reg, &imme, hardware)?;
+        Ok(())
+    } else {
+        Err(undecodable())
     }
 }
 
-pub fn execute_arithmic_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    match binary_data[0] {
-        // Reg./Memory with Register to Either
-        0b00000000..=0b00000011
-        | 0b00010000..=0b00010011
-        | 0b00101000..=0b00101011
-        | 0b00011000..=0b00011011
-        | 0b00111000..=0b00111011 => {
-            let d = (binary_data[0] & 0b00000010) >> 1;
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11111111)
-            {
-                let value_1 =
-                    read_from_address(w == 0b1, &reg, hardware).expect("Unable to retrieve data");
-                let value_2 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                let result = match (((binary_data[0] & 0b00111000) >> 3), value_1, value_2) {
-                    (0b000, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_add(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b000, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_add(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b010, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8)
-                            .carrying_add(value_2 as i8, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b010, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16)
-                            .carrying_add(value_2 as i16, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b101, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_sub(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b101, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_sub(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b011, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8)
-                            .borrowing_sub(value_2 as i8, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b011, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16)
-                            .borrowing_sub(value_2 as i16, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b111, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_sub(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", (value_2 as i8) < (value_1 as i8));
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b111, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_sub(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", (value_2 as i16) < (value_1 as i16));
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    _ => panic!("Instruction decode error"),
-                };
-                match Direction::from(d) {
-                    Direction::FromReg => write_to_address(&r_m, &result, hardware),
-                    Direction::ToReg => write_to_address(&reg, &result, hardware),
-                };
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Immediate to Accumulator
-        0b00000100 | 0b00000101 | 0b00010100 | 0b00010101 | 0b00101100 | 0b00101101
-        | 0b00011100 | 0b00011101 | 0b00111100 | 0b00111101 => {
-            let (_, imme) = decode_data(w == 0b1, false, &binary_data[1..]);
-            match (
-                ((binary_data[0] & 0b00111100) >> 2),
-                imme.expect("Unable to retrieve data"),
-            ) {
-                (0b0001, Immediate::UnsignedByte(imme)) => {
-                    let al = hardware.clone().read_from_byte_register(AL);
-                    let (result, overflow) = (al as i8).overflowing_add(imme as i8);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.write_to_byte_register(AL, result as u8)
-                }
-                (0b0001, Immediate::UnsignedWord(imme)) => {
-                    let (result, overflow) = (hardware.ax as i16).overflowing_add(imme as i16);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.ax = result as u16;
-                }
-                (0b0101, Immediate::UnsignedByte(imme)) => {
-                    let al = hardware.clone().read_from_byte_register(AL);
-                    let (result, overflow) =
-                        (al as i8).carrying_add(imme as i8, hardware.clone().read_flags("CF"));
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.write_to_byte_register(AL, result as u8)
-                }
-                (0b0101, Immediate::UnsignedWord(imme)) => {
-                    let (result, overflow) = (hardware.ax as i16)
-                        .carrying_add(imme as i16, hardware.clone().read_flags("CF"));
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.ax = result as u16;
-                }
-                (0b1011, Immediate::UnsignedByte(imme)) => {
-                    let al = hardware.clone().read_from_byte_register(AL);
-                    let (result, overflow) = (al as i8).overflowing_sub(imme as i8);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.write_to_byte_register(AL, result as u8)
-                }
-                (0b1011, Immediate::UnsignedWord(imme)) => {
-                    let (result, overflow) = (hardware.ax as i16).overflowing_sub(imme as i16);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.ax = result as u16;
-                }
-                (0b0111, Immediate::UnsignedByte(imme)) => {
-                    let al = hardware.clone().read_from_byte_register(AL);
-                    let (result, overflow) =
-                        (al as i8).borrowing_sub(imme as i8, hardware.clone().read_flags("CF"));
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.write_to_byte_register(AL, result as u8)
-                }
-                (0b0111, Immediate::UnsignedWord(imme)) => {
-                    let (result, overflow) = (hardware.ax as i16)
-                        .borrowing_sub(imme as i16, hardware.clone().read_flags("CF"));
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", overflow);
-                    hardware.ax = result as u16;
-                }
-                (0b1111, Immediate::UnsignedByte(imme)) => {
-                    let al = hardware.clone().read_from_byte_register(AL);
-                    let (result, overflow) = (al as i8).overflowing_sub(imme as i8);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", (al as i8) < (imme as i8));
-                    hardware.write_to_byte_register(AL, result as u8)
-                }
-                (0b1111, Immediate::UnsignedWord(imme)) => {
-                    let (result, overflow) = (hardware.ax as i16).overflowing_sub(imme as i16);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                    hardware.write_flags("CF", (hardware.ax as i8) < (imme as i8));
-                    hardware.ax = result as u16;
-                }
-                _ => panic!("Instruction decode error"),
-            };
+pub fn execute_load_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    match instruction {
+        Instruction::AddressToAddress(LEA, _, reg, r_m) => {
+            let offset = calculate_offset(r_m, hardware).ok_or_else(undecodable)?;
+            write_to_address(reg, &Immediate::UnsignedWord(offset), hardware)?
         }
-        // Immediate to Register/Memory
-        0b10000000..=0b10000011 => {
-            let s = (binary_data[0] & 0b00000010) >> 1;
-            if let (rl, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-                let value_1 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                let value_2 = match decode_data(
-                    (w == 0b1) & (s != 0b1),
-                    (s == 0b1) & ((binary_data[2 + rl] as i8) < 0i8),
-                    &binary_data[(2 + rl)..],
-                ) {
-                    (_, Some(Immediate::SignedByte(imme))) => Immediate::UnsignedByte(imme as u8),
-                    (_, Some(Immediate::SignedWord(imme))) => Immediate::UnsignedWord(imme as u16),
-                    (_, Some(imme)) => imme,
-                    _ => panic!("Unable to retrieve data"),
-                };
-                let result = match (((binary_data[1] & 0b00111000) >> 3), value_1, value_2) {
-                    (0b000, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_add(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b000, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_add(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b010, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8)
-                            .carrying_add(value_2 as i8, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b010, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16)
-                            .carrying_add(value_2 as i16, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b101, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_sub(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b101, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_sub(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b011, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8)
-                            .borrowing_sub(value_2 as i8, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b011, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16)
-                            .borrowing_sub(value_2 as i16, hardware.clone().read_flags("CF"));
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", overflow);
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    (0b111, Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let (result, overflow) = (value_1 as i8).overflowing_sub(value_2 as i8);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", (value_2 as i8) < (value_1 as i8));
-                        Immediate::UnsignedByte(result as u8)
-                    }
-                    (0b111, Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let (result, overflow) = (value_1 as i16).overflowing_sub(value_2 as i16);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", result < 0);
-                        hardware.write_flags("OF", overflow);
-                        hardware.write_flags("CF", (value_2 as i16) < (value_1 as i16));
-                        Immediate::UnsignedWord(result as u16)
-                    }
-                    _ => panic!("Instruction decode error"),
-                };
-                write_to_address(&r_m, &result, hardware)
+        // LDS / LES, a far pointer in memory, offset first and segment after it
+        Instruction::AddressToAddress(mnemonic @ (LDS | LES), _, reg, r_m) if r_m.is_memory() => {
+            let address = calculate_effective_address(r_m, hardware).ok_or_else(memory_fault)?;
+            let offset = hardware.read_word_from_memory(address);
+            let segment = hardware.read_word_from_memory(address + 2);
+            write_to_address(reg, &Immediate::UnsignedWord(offset), hardware)?;
+            if matches!(mnemonic, LDS) {
+                hardware.ds = segment;
             } else {
-                panic!("Instruction decode error")
+                hardware.es = segment;
             }
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_increase_decrease_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // Register/Memory
-        0b11111110 | 0b11111111 => {
-            let w = binary_data[0] & 0b00000001;
-            if let (_, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-                if (binary_data[1] & 0b00111000) == 0b00000000 {
-                    let result = match read_from_address(w == 0b1, &r_m, hardware)
-                        .expect("Unable to retrieve data")
-                    {
-                        Immediate::UnsignedWord(imme) => {
-                            let (result, overflow) = (imme as i16).overflowing_add(1i16);
-                            hardware.write_flags("ZF", result == 0);
-                            hardware.write_flags("SF", result < 0);
-                            hardware.write_flags("OF", overflow);
-                            Immediate::UnsignedWord(result as u16)
-                        }
-                        Immediate::UnsignedByte(imme) => {
-                            let (result, overflow) = (imme as i8).overflowing_add(1i8);
-                            hardware.write_flags("ZF", result == 0);
-                            hardware.write_flags("SF", result < 0);
-                            hardware.write_flags("OF", overflow);
-                            Immediate::UnsignedByte(result as u8)
-                        }
-                        _ => unreachable!(),
-                    };
-                    write_to_address(&r_m, &result, hardware);
-                } else {
-                    let result = match read_from_address(w == 0b1, &r_m, hardware)
-                        .expect("Unable to retrieve data")
-                    {
-                        Immediate::UnsignedWord(imme) => {
-                            let (result, overflow) = (imme as i16).overflowing_sub(1i16);
-                            hardware.write_flags("ZF", result == 0);
-                            hardware.write_flags("SF", result < 0);
-                            hardware.write_flags("OF", overflow);
-                            Immediate::UnsignedWord(result as u16)
-                        }
-                        Immediate::UnsignedByte(imme) => {
-                            let (result, overflow) = (imme as i8).overflowing_sub(1i8);
-                            hardware.write_flags("ZF", result == 0);
-                            hardware.write_flags("SF", result < 0);
-                            hardware.write_flags("OF", overflow);
-                            Immediate::UnsignedByte(result as u8)
-                        }
-                        _ => unreachable!(),
-                    };
-                    write_to_address(&r_m, &result, hardware);
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
+// ADD, ADC, SUB, SBB and CMP share their operand forms with AND, OR, XOR and TEST
+pub fn execute_arithmic_instruction(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, destination, source) = match instruction {
+        // Reg./Memory with Register to Either
+        &Instruction::AddressToAddress(mnemonic, direction, reg, r_m) => {
+            let (destination, source) = match direction {
+                Direction::FromReg => (r_m, reg),
+                Direction::ToReg => (reg, r_m),
+            };
+            let source = read_from_address(operand_size(&reg, word_mode), &source, hardware)
+                .ok_or_else(memory_fault)?;
+            (mnemonic, destination, operand(&source))
         }
-        // Register
-        0b01000000..=0b01000111 | 0b01001000..=0b01001111 => {
-            if let (_, Some(reg), None) = Addressing::decode(0b1, binary_data, 0b00000111) {
-                if (binary_data[0] & 0b11111000) == 0b01000000 {
-                    let imme = match read_from_address(true, &reg, hardware)
-                        .expect("Unable to retrieve data")
-                    {
-                        Immediate::UnsignedWord(imme) => imme as i16,
-                        _ => unreachable!(),
-                    };
-                    let (result, overflow) = imme.overflowing_add(1i16);
-                    write_to_address(&reg, &Immediate::UnsignedWord(result as u16), hardware);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                } else {
-                    let imme = match read_from_address(true, &reg, hardware)
-                        .expect("Unable to retrieve data")
-                    {
-                        Immediate::UnsignedWord(imme) => imme as i16,
-                        _ => unreachable!(),
-                    };
-                    let (result, overflow) = imme.overflowing_sub(1i16);
-                    write_to_address(&reg, &Immediate::UnsignedWord(result as u16), hardware);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", result < 0);
-                    hardware.write_flags("OF", overflow);
-                }
-            } else {
-                panic!("Instruction decode error")
-            }
+        // Immediate to Accumulator / Register/Memory, a sign-extended byte counts as a word
+        &Instruction::ImmediateToAddress(mnemonic, destination, Numerical::Imme(immediate)) => {
+            (mnemonic, destination, operand(&immediate.extend()))
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
+    };
+    let value = read_from_address(
+        operand_size(&destination, word_mode),
+        &destination,
+        hardware,
+    )
+    .ok_or_else(memory_fault)?;
+    let operation = Operation::from_mnemonic(mnemonic).ok_or_else(undecodable)?;
+    let result = operate(operation, &value, source, hardware);
+    if operation.writes_back() {
+        write_to_address(&destination, &result, hardware)?;
     }
+    Ok(())
 }
 
-pub fn execute_negation_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    let (_, _, r_m) = Addressing::decode(w, &binary_data[1..], 0b11000111);
-    let r_m = r_m.expect("Instruction decode error");
-    match read_from_address(w == 0b1, &r_m, hardware).expect("Unable retrieve data") {
-        Immediate::UnsignedWord(imme) => {
-            let (result, overflow) = (imme as i16).overflowing_neg();
-            hardware.write_flags("ZF", result == 0);
-            hardware.write_flags("SF", result < 0);
-            hardware.write_flags("OF", overflow);
-            hardware.write_flags("CF", imme != 0);
-            write_to_address(&r_m, &Immediate::UnsignedWord(result as u16), hardware);
-        }
-        Immediate::UnsignedByte(imme) => {
-            let (result, overflow) = (imme as i8).overflowing_neg();
-            hardware.write_flags("ZF", result == 0);
-            hardware.write_flags("SF", result < 0);
-            hardware.write_flags("OF", overflow);
-            hardware.write_flags("CF", imme != 0);
-            write_to_address(&r_m, &Immediate::UnsignedByte(result as u8), hardware);
-        }
-        _ => panic!("Instruction decode error"),
+// INC, DEC and NEG, the operand is both source and destination
+pub fn execute_increase_decrease_instruction(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (operation, address) = match instruction {
+        &Instruction::WithAddress(mnemonic @ (INC | DEC | NEG), address) => (
+            Operation::from_mnemonic(mnemonic).ok_or_else(undecodable)?,
+            address,
+        ),
+        _ => return Err(undecodable()),
     };
+    let value = read_from_address(operand_size(&address, word_mode), &address, hardware)
+        .ok_or_else(memory_fault)?;
+    let result = operate(operation, &value, 0x0001, hardware);
+    write_to_address(&address, &result, hardware)?;
+    Ok(())
 }
 
-pub fn execute_multiply_divide_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    if binary_data[0] == 0b11110110 | 0b11110111 {
-        if let (_, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-            let source = read_from_address(w == 0b1, &r_m, hardware).expect("Unable retrieve data");
-            match binary_data[1] & 0b00111000 {
-                0b00100000 => {
-                    let operand = read_from_address(
-                        w == 0b1,
-                        &Addressing::RegisterAddressing(
-                            Register::decode(w == 0b1, true, 0b000).unwrap(),
-                        ),
-                        hardware,
-                    )
-                    .expect("Unable to retrieve data");
-                    match (operand, source) {
-                        (Immediate::UnsignedByte(operand), Immediate::UnsignedByte(source)) => {
-                            let (result, _) = i16::from(operand).overflowing_mul(source as i16);
-                            hardware.ax = result as u16;
-                            let overflow = (hardware.ax >> 8) != 0;
-                            hardware.write_flags("OF", overflow);
-                            hardware.write_flags("CF", overflow);
-                        }
-                        (Immediate::UnsignedWord(operand), Immediate::UnsignedWord(source)) => {
-                            let (result, _) = i32::from(operand).overflowing_mul(i32::from(source));
-                            hardware.dx = ((result >> 16) & 0xFFFF) as u16;
-                            hardware.ax = (result & 0xFFFF) as u16;
-                            let overflow = hardware.dx != 0;
-                            hardware.write_flags("OF", overflow);
-                            hardware.write_flags("CF", overflow);
-                        }
-                        _ => unreachable!(),
-                    }
-                }
-                0b00101000 => todo!("IMUL"),
-                0b00110000 => match source {
-                    Immediate::UnsignedByte(source) => {
-                        let source = source as i16;
-                        let operand = hardware.ax as i16;
-                        let (quotient, _) = operand.overflowing_div(source);
-                        let (remainder, _) = operand.overflowing_rem(source);
-                        hardware.write_to_byte_register(AL, ((quotient as u16) & 0xff) as u8);
-                        hardware.write_to_byte_register(AH, ((remainder as u16) & 0xff) as u8);
-                    }
-                    Immediate::UnsignedWord(source) => {
-                        let source = i32::from(source);
-                        let operand = i32::from(hardware.dx) << 16 + i32::from(hardware.ax);
-                        let (quotient, _) = operand.overflowing_div(source);
-                        let (remainder, _) = operand.overflowing_rem(source);
-                        hardware.write_to_word_register(AX, ((quotient as u32) & 0xffff) as u16);
-                        hardware.write_to_word_register(DX, ((remainder as u32) & 0xffff) as u16);
-                    }
-                    _ => unreachable!(),
-                },
-                0b00111000 => todo!("IDIV"),
-                _ => panic!("Instruction decode error"),
+pub fn execute_multiply_divide_instruction(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, r_m) = match instruction {
+        &Instruction::WithAddress(mnemonic, r_m) => (mnemonic, r_m),
+        _ => return Err(undecodable()),
+    };
+    let word_mode = operand_size(&r_m, word_mode);
+    let source = operand(&read_from_address(word_mode, &r_m, hardware).ok_or_else(memory_fault)?);
+    let (ax, dx) = (
+        hardware.read_from_word_register(AX),
+        hardware.read_from_word_register(DX),
+    );
+    match (mnemonic, word_mode) {
+        (MUL, false) => {
+            let result = (ax & 0x00ff) * source;
+            hardware.write_to_word_register(AX, result);
+            hardware.write_flag(Flag::OF, (result >> 8) != 0);
+            hardware.write_flag(Flag::CF, (result >> 8) != 0);
+        }
+        (MUL, true) => {
+            let result = u32::from(ax) * u32::from(source);
+            hardware.write_to_word_register(AX, result as u16);
+            hardware.write_to_word_register(DX, (result >> 16) as u16);
+            hardware.write_flag(Flag::OF, (result >> 16) != 0);
+            hardware.write_flag(Flag::CF, (result >> 16) != 0);
+        }
+        // IMUL, CF and OF tell whether the upper half is more than the sign extension of the lower
+        (IMUL, false) => {
+            let result = i16::from(ax as u8 as i8) * i16::from(source as u8 as i8);
+            hardware.write_to_word_register(AX, result as u16);
+            let overflow = result != i16::from(result as i8);
+            hardware.write_flag(Flag::OF, overflow);
+            hardware.write_flag(Flag::CF, overflow);
+        }
+        (IMUL, true) => {
+            let result = i32::from(ax as i16) * i32::from(source as i16);
+            hardware.write_to_word_register(AX, result as u16);
+            hardware.write_to_word_register(DX, (result >> 16) as u16);
+            let overflow = result != i32::from(result as i16);
+            hardware.write_flag(Flag::OF, overflow);
+            hardware.write_flag(Flag::CF, overflow);
+        }
+        // DIV, a zero divisor or a quotient too wide for AL / AX is a divide error
+        (DIV, false) => {
+            if source == 0 || ax / source > 0xff {
+                return Err(divide_error());
             }
-        } else {
-            panic!("Instruction decode error")
+            hardware.write_to_byte_register(AL, (ax / source) as u8);
+            hardware.write_to_byte_register(AH, (ax % source) as u8);
         }
-    } else {
-        panic!("Instruction decode error")
-    }
-}
-
-pub fn execute_not_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    if binary_data[0] != 0b11110110 | 0b11110111 {
-        panic!("Instruction decode error")
-    }
-    match Addressing::decode(w, &binary_data[1..], 0b11000111) {
-        (_, None, Some(r_m)) if match_reg(binary_data[1], &[0b011]) => {
-            match read_from_address(w == 0b1, &r_m, hardware).expect("Unable retrieve data") {
-                Immediate::UnsignedByte(imme) => {
-                    write_to_address(&r_m, &Immediate::UnsignedByte(!imme), hardware)
-                }
-                Immediate::UnsignedWord(imme) => {
-                    write_to_address(&r_m, &Immediate::UnsignedWord(!imme), hardware)
-                }
-                _ => unreachable!(),
+        (DIV, true) => {
+            let dividend = (u32::from(dx) << 16) | u32::from(ax);
+            let source = u32::from(source);
+            if source == 0 || dividend / source > 0xffff {
+                return Err(divide_error());
+            }
+            hardware.write_to_word_register(AX, (dividend / source) as u16);
+            hardware.write_to_word_register(DX, (dividend % source) as u16);
+        }
+        // IDIV, the remainder takes the sign of the dividend and the 8086 rejects -128 / -32768
+        (IDIV, false) => {
+            let dividend = i32::from(ax as i16);
+            let source = i32::from(source as u8 as i8);
+            if source == 0 || (dividend / source).abs() > 0x7f {
+                return Err(divide_error());
             }
+            hardware.write_to_byte_register(AL, (dividend / source) as u8);
+            hardware.write_to_byte_register(AH, (dividend % source) as u8);
         }
-        _ => panic!("Instruction decode error"),
+        (IDIV, true) => {
+            let dividend = ((i64::from(dx) << 16) | i64::from(ax)) as i32 as i64;
+            let source = i64::from(source as i16);
+            if source == 0 || (dividend / source).abs() > 0x7fff {
+                return Err(divide_error());
+            }
+            hardware.write_to_word_register(AX, (dividend / source) as u16);
+            hardware.write_to_word_register(DX, (dividend % source) as u16);
+        }
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_shift_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let v = (binary_data[0] & 0b00000010) >> 1;
-    let w = binary_data[0] & 0b00000001;
-    if let (_, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-        let source = read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-        let offset = if v == 0b1 {
-            hardware.clone().read_from_byte_register(CL) & 0x1f
-        } else {
-            1u8
-        };
-        match binary_data[1] & 0b00111000 {
-            0b00100000 => match source {
-                Immediate::UnsignedByte(source) => {
-                    let (result, _) = source.overflowing_shl((offset - 1) as u32);
-                    let carry = (result & 0x80) >> 7;
-                    let (result, _) = source.overflowing_shl(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i8) < 0);
-                    hardware.write_flags("OF", ((result & 0x80) >> 7) != carry);
-                    hardware.write_flags("CF", result == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedByte(result), hardware);
-                }
-                Immediate::UnsignedWord(source) => {
-                    let (result, _) = source.overflowing_shl((offset - 1) as u32);
-                    let carry = (result & 0x8000) >> 15;
-                    let (result, _) = source.overflowing_shl(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i16) < 0);
-                    hardware.write_flags("OF", ((result & 0x8000) >> 15) != carry);
-                    hardware.write_flags("CF", result == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedWord(result), hardware);
-                }
-                _ => unreachable!(),
-            },
-            0b00101000 => match source {
-                Immediate::UnsignedByte(source) => {
-                    let (result, _) = source.overflowing_shr((offset - 1) as u32);
-                    let carry = result & 0x01;
-                    let (result, _) = source.overflowing_shr(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i8) < 0);
-                    if v == 0b0 {
-                        hardware.write_flags("OF", ((result & 0x80) >> 7) == 0b1);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedByte(result), hardware);
-                }
-                Immediate::UnsignedWord(source) => {
-                    let (result, _) = source.overflowing_shr((offset - 1) as u32);
-                    let carry = result & 0x0001;
-                    let (result, _) = source.overflowing_shr(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i16) < 0);
-                    if v == 0b0 {
-                        hardware.write_flags("OF", ((result & 0x8000) >> 15) == 0b1);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedWord(result), hardware);
-                }
-                _ => unreachable!(),
-            },
-            0b00111000 => match source {
-                Immediate::UnsignedByte(source) => {
-                    let source = source as i8;
-                    let (result, _) = source.overflowing_shr((offset - 1) as u32);
-                    let carry = (result as u8) & 0x01;
-                    let (result, _) = source.overflowing_shr(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i8) < 0);
-                    if v == 0b0 {
-                        hardware.write_flags("OF", false);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedByte(result as u8), hardware);
-                }
-                Immediate::UnsignedWord(source) => {
-                    let source = source as i16;
-                    let (result, _) = source.overflowing_shr((offset - 1) as u32);
-                    let carry = (result as u16) & 0x0001;
-                    let (result, _) = source.overflowing_shr(1);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i16) < 0);
-                    if v == 0b0 {
-                        hardware.write_flags("OF", false);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedWord(result as u16), hardware);
-                }
-                _ => unreachable!(),
-            },
-            0b00000000 => todo!("ROL"),
-            0b00001000 => todo!("ROR"),
-            0b00010000 => match source {
-                Immediate::UnsignedByte(source) => {
-                    let (result, _) = source.overflowing_shl((offset - 1) as u32);
-                    let carry = (result & 0x80) >> 7;
-                    let (mut result, _) = source.overflowing_shl(1);
-                    result = if hardware.clone().read_flags("CF") {
-                        result | 0x01
-                    } else {
-                        result & 0xfe
-                    };
-                    if v == 0b0 {
-                        hardware.write_flags("OF", (((result & 0x80) >> 7) ^ carry) == 0b1);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedByte(result), hardware);
-                }
-                Immediate::UnsignedWord(source) => {
-                    let (result, _) = source.overflowing_shl((offset - 1) as u32);
-                    let carry = (result & 0x8000) >> 15;
-                    let (mut result, _) = source.overflowing_shl(1);
-                    result = if hardware.clone().read_flags("CF") {
-                        result | 0x0001
-                    } else {
-                        result & 0xfffe
-                    };
-                    if v == 0b0 {
-                        hardware.write_flags("OF", (((result & 0x8000) >> 15) ^ carry) == 0b1);
-                    }
-                    hardware.write_flags("CF", carry == 0b1);
-                    write_to_address(&r_m, &Immediate::UnsignedWord(result), hardware);
-                }
-                _ => unreachable!(),
-            },
-            0b00011000 => todo!("RCR"),
-            _ => panic!("Instruction decode error"),
-        };
-    } else {
-        panic!("Instruction decode error")
+pub fn execute_not_instruction(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let r_m = match instruction {
+        Instruction::WithAddress(NOT, r_m) => r_m,
+        _ => return Err(undecodable()),
+    };
+    match read_from_address(operand_size(r_m, word_mode), r_m, hardware).ok_or_else(memory_fault)? {
+        Immediate::UnsignedByte(imme) => {
+            write_to_address(r_m, &Immediate::UnsignedByte(!imme), hardware)?
+        }
+        Immediate::UnsignedWord(imme) => {
+            write_to_address(r_m, &Immediate::UnsignedWord(!imme), hardware)?
+        }
+        _ => return Err(unimplemented()),
     }
+    Ok(())
 }
 
-pub fn execute_logic_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    match binary_data[0] {
-        // Reg./Memory and Register to Either
-        0b00100000..=0b00100011 | 0b00001000..=0b00001011 | 0b00110000..=0b00110011 => {
-            let d = (binary_data[0] & 0b00000010) >> 1;
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11111111)
-            {
-                let value_1 =
-                    read_from_address(w == 0b1, &reg, hardware).expect("Unable to retrieve data");
-                let value_2 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                match (value_1, value_2) {
-                    (Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let result = match binary_data[0] & 0b11111100 {
-                            0b00100000 => value_1 & value_2,
-                            0b00001000 => value_1 | value_2,
-                            0b00110000 => value_1 ^ value_2,
-                            _ => panic!("Instruction decode error"),
-                        };
-                        match Direction::from(d) {
-                            Direction::FromReg => {
-                                write_to_address(&r_m, &&Immediate::UnsignedByte(result), hardware)
-                            }
-                            Direction::ToReg => {
-                                write_to_address(&reg, &&Immediate::UnsignedByte(result), hardware)
-                            }
-                        };
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", (result as i8) < 0);
-                    }
-                    (Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let result = match binary_data[0] & 0b11111100 {
-                            0b00100000 => value_1 & value_2,
-                            0b00001000 => value_1 | value_2,
-                            0b00110000 => value_1 ^ value_2,
-                            _ => panic!("Instruction decode error"),
-                        };
-                        match Direction::from(d) {
-                            Direction::FromReg => {
-                                write_to_address(&r_m, &Immediate::UnsignedWord(result), hardware)
-                            }
-                            Direction::ToReg => {
-                                write_to_address(&reg, &Immediate::UnsignedWord(result), hardware)
-                            }
-                        };
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", (result as i16) < 0);
-                    }
-                    _ => unreachable!(),
-                };
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Immediate to Accumulator
-        0b00100100 | 0b00100101 | 0b00001100 | 0b00001101 | 0b00110100 | 0b00110101 => {
-            let value_1 = read_from_address(
-                w == 0b1,
-                &Addressing::RegisterAddressing(Register::decode(w == 0b1, true, 0b000).unwrap()),
-                hardware,
-            )
-            .expect("Unable to retrieve data");
-            let (_, value_2) = decode_data(w == 0b1, true, &binary_data[1..]);
-            let value_2 = value_2.expect("Unable to retrieve data");
-            match (value_1, value_2) {
-                (Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                    let result = match binary_data[0] & 0b11111110 {
-                        0b00100100 => value_1 & value_2,
-                        0b00001100 => value_1 | value_2,
-                        0b00110100 => value_1 ^ value_2,
-                        _ => panic!("Instruction decode error"),
-                    };
-                    hardware.write_to_byte_register(AL, result);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i8) < 0);
-                }
-                (Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                    let result = match binary_data[0] & 0b11111110 {
-                        0b00100100 => value_1 & value_2,
-                        0b00001100 => value_1 | value_2,
-                        0b00110100 => value_1 ^ value_2,
-                        _ => panic!("Instruction decode error"),
-                    };
-                    hardware.write_to_word_register(AX, result);
-                    hardware.write_flags("ZF", result == 0);
-                    hardware.write_flags("SF", (result as i16) < 0);
-                }
-                _ => unreachable!(),
-            };
+pub fn execute_shift_instruction(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, r_m, count) = match instruction {
+        // count in CL
+        &Instruction::AddressToAddress(mnemonic, _, _, r_m) => {
+            (mnemonic, r_m, hardware.read_from_byte_register(CL))
         }
-        // Immediate to Register/Memory
-        0b10000000..=0b10000011 => {
-            if let (rl, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-                let value_1 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                let (_, value_2) = decode_data(w == 0b1, false, &binary_data[(2 + rl)..]);
-                let value_2 = value_2.expect("Unable to retrieve data");
-                match (value_1, value_2) {
-                    (Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-                        let result = match binary_data[1] & 0b00111000 {
-                            0b00100000 => value_1 & value_2,
-                            0b00001000 => value_1 | value_2,
-                            0b00110000 => value_1 ^ value_2,
-                            _ => panic!("Instruction decode error"),
-                        };
-                        write_to_address(&r_m, &Immediate::UnsignedByte(result), hardware);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", (result as i8) < 0);
-                    }
-                    (Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-                        let result = match binary_data[1] & 0b00111000 {
-                            0b00100000 => value_1 & value_2,
-                            0b00001000 => value_1 | value_2,
-                            0b00110000 => value_1 ^ value_2,
-                            _ => panic!("Instruction decode error"),
-                        };
-                        write_to_address(&r_m, &Immediate::UnsignedWord(result), hardware);
-                        hardware.write_flags("ZF", result == 0);
-                        hardware.write_flags("SF", (result as i16) < 0);
-                    }
-                    _ => unreachable!(),
-                };
-            } else {
-                panic!("Instruction decode error")
-            }
+        // count of 1
+        &Instruction::ImmediateToAddress(mnemonic, r_m, Numerical::Imme(count)) => {
+            (mnemonic, r_m, operand(&count) as u8)
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
     };
-    hardware.write_flags("OF", false);
-    hardware.write_flags("CF", false);
+    let source = read_from_address(operand_size(&r_m, word_mode), &r_m, hardware)
+        .ok_or_else(memory_fault)?;
+    let operation = Operation::from_mnemonic(mnemonic).ok_or_else(undecodable)?;
+    let result = operate(operation, &source, u16::from(count), hardware);
+    write_to_address(&r_m, &result, hardware)?;
+    Ok(())
 }
 
-pub fn execute_test_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    let (value_1, value_2) = match binary_data[0] & 0b11111110 {
-        // Register/Memory and Register
-        0b10000100 | 0b10000101 => {
-            if let (_, Some(reg), Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11111111)
-            {
-                let value_1 =
-                    read_from_address(w == 0b1, &reg, hardware).expect("Unable to retrieve data");
-                let value_2 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                (value_1, value_2)
-            } else {
-                panic!("Instruction decode error")
-            }
-        }
-        // Immediate Data and Register/Memory
-        0b11110110 | 0b11110111 if (binary_data[1] & 0b00111000) == 0b0 => {
-            if let (rl, None, Some(r_m)) = Addressing::decode(w, &binary_data[1..], 0b11000111) {
-                let value_1 =
-                    read_from_address(w == 0b1, &r_m, hardware).expect("Unable to retrieve data");
-                let (_, value_2) = decode_data(w == 0b1, false, &binary_data[(2 + rl)..]);
-                (value_1, value_2.expect("Unable to retrieve data"))
-            } else {
-                panic!("Instruction decode error")
-            }
+fn write_to_memory(address: u32, immediate: &Immediate, hardware: &mut Hardware) {
+    match immediate {
+        Immediate::UnsignedByte(_) | Immediate::SignedByte(_) => {
+            hardware.write_byte_to_memory(address, operand(immediate) as u8)
         }
-        // Immediate Data and Accumulator
-        0b10101000 | 0b10101001 => {
-            let value_1 = read_from_address(
-                w == 0b1,
-                &Addressing::RegisterAddressing(Register::decode(w == 0b1, true, 0b000).unwrap()),
-                hardware,
-            )
-            .expect("Unable to retrieve data");
-            let (_, value_2) = decode_data(w == 0b1, false, &binary_data[1..]);
-            (value_1, value_2.expect("Unable to retrieve data"))
+        _ => hardware.write_word_to_memory(address, operand(immediate)),
+    }
+}
+
+pub fn execute_string_instruction(
+    mnemonic: Mnemonic,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let word_mode = matches!(mnemonic, MOVSW | CMPSW | SCASW | LODSW | STOSW);
+    // DF picks the direction SI / DI move in after each element
+    let step = match (word_mode, hardware.read_flag(Flag::DF)) {
+        (false, false) => 0x0001,
+        (false, true) => 0xffff,
+        (true, false) => 0x0002,
+        (true, true) => 0xfffe,
+    };
+    // DS:SI may be overridden, ES:DI may not
+    let source_address =
+        hardware.physical_address(hardware.segment_override.unwrap_or(DS), hardware.si);
+    let target_address = hardware.physical_address(ES, hardware.di);
+    let read = |address: u32, hardware: &Hardware| {
+        if word_mode {
+            Immediate::UnsignedWord(hardware.read_word_from_memory(address))
+        } else {
+            Immediate::UnsignedByte(hardware.read_byte_from_memory(address))
         }
-        _ => panic!("Instruction decode error"),
     };
-    match (value_1, value_2) {
-        (Immediate::UnsignedByte(value_1), Immediate::UnsignedByte(value_2)) => {
-            let result = (value_1 & value_2) as i8;
-            hardware.write_flags("ZF", result == 0);
-            hardware.write_flags("SF", result < 0);
+    let accumulator =
+        Addressing::RegisterAddressing(Register::decode(word_mode, true, 0b000).unwrap());
+    match mnemonic {
+        MOVSB | MOVSW => {
+            let imme = read(source_address, hardware);
+            write_to_memory(target_address, &imme, hardware);
+            hardware.si = hardware.si.wrapping_add(step);
+            hardware.di = hardware.di.wrapping_add(step);
         }
-        (Immediate::UnsignedWord(value_1), Immediate::UnsignedWord(value_2)) => {
-            let result = (value_1 & value_2) as i16;
-            hardware.write_flags("ZF", result == 0);
-            hardware.write_flags("SF", result < 0);
+        // [SI] - [DI]
+        CMPSB | CMPSW => {
+            let destination = read(source_address, hardware);
+            let source = operand(&read(target_address, hardware));
+            operate(Operation::Cmp, &destination, source, hardware);
+            hardware.si = hardware.si.wrapping_add(step);
+            hardware.di = hardware.di.wrapping_add(step);
         }
-        _ => unreachable!(),
-    };
-    hardware.write_flags("OF", false);
-    hardware.write_flags("CF", false);
+        // accumulator - [DI]
+        SCASB | SCASW => {
+            let destination =
+                read_from_address(word_mode, &accumulator, hardware).ok_or_else(memory_fault)?;
+            let source = operand(&read(target_address, hardware));
+            operate(Operation::Cmp, &destination, source, hardware);
+            hardware.di = hardware.di.wrapping_add(step);
+        }
+        LODSB | LODSW => {
+            let imme = read(source_address, hardware);
+            write_to_address(&accumulator, &imme, hardware)?;
+            hardware.si = hardware.si.wrapping_add(step);
+        }
+        STOSB | STOSW => {
+            let imme =
+                read_from_address(word_mode, &accumulator, hardware).ok_or_else(memory_fault)?;
+            write_to_memory(target_address, &imme, hardware);
+            hardware.di = hardware.di.wrapping_add(step);
+        }
+        _ => return Err(undecodable()),
+    }
+    Ok(())
 }
 
-pub fn execute_string_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let w = binary_data[0] & 0b00000001;
-    match binary_data[0] {
-        0b10100100 | 0b10100101 => {
-            let source_address = hardware.ds << 4 + hardware.si;
-            let target_address = hardware.es << 4 + hardware.di;
-            if w == 0b0 {
-                hardware.write_byte_to_memory(
-                    target_address,
-                    hardware
-                        .clone()
-                        .read_byte_from_memory(source_address)
-                        .expect("Unable to retrieve data"),
-                );
-                if hardware.clone().read_flags("DF") {
-                    hardware.si -= 1;
-                    hardware.di -= 1;
-                } else {
-                    hardware.si += 1;
-                    hardware.di += 1;
-                }
-            } else {
-                hardware.write_word_to_memory(
-                    target_address,
-                    hardware
-                        .clone()
-                        .read_word_from_memory(source_address)
-                        .expect("Unable to retrieve data"),
-                );
-                if hardware.clone().read_flags("DF") {
-                    hardware.si -= 2;
-                    hardware.di -= 2;
-                } else {
-                    hardware.si += 2;
-                    hardware.di += 2;
-                }
+pub fn execute_repeat_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    // only CMPS and SCAS look at ZF, REPE repeats while equal and REPNE while not equal
+    let (until_zero, mnemonic) = match instruction {
+        &Instruction::WithInstruction(REP, mnemonic) => (None, mnemonic),
+        &Instruction::WithInstruction(REPE, mnemonic) => (Some(false), mnemonic),
+        &Instruction::WithInstruction(REPNE, mnemonic) => (Some(true), mnemonic),
+        _ => return Err(undecodable()),
+    };
+    while hardware.cx != 0 {
+        execute_string_instruction(mnemonic, hardware)?;
+        hardware.cx -= 1;
+        if let Some(until_zero) = until_zero {
+            if hardware.read_flag(Flag::ZF) == until_zero {
+                break;
             }
         }
-        0b10100110 | 0b10100111 => todo!("CMPS"),
-        0b10101110 | 0b10101111 => todo!("SCAS"),
-        0b10101100 | 0b10101101 => todo!("LODSW"),
-        0b10101010 | 0b10101011 => todo!("STOSW"),
-        _ => panic!("Instruction decode error"),
     }
+    Ok(())
 }
 
-pub fn execute_repeat_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        0b11110010 => {
-            while hardware.cx != 0 {
-                execute_string_instruction(&binary_data[1..], hardware);
-                hardware.cx -= 1;
-            }
+pub fn execute_adjust_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, base) = match instruction {
+        // AAM and AAD only carry their base when it isn't 10
+        &Instruction::Standalone(mnemonic) => (mnemonic, 10),
+        &Instruction::WithImmediate(mnemonic, Numerical::Imme(Immediate::UnsignedByte(base))) => {
+            (mnemonic, base)
         }
-        0b11110011 => {
-            while (hardware.cx != 0) | hardware.clone().read_flags("ZF") {
-                execute_string_instruction(&binary_data[1..], hardware);
-                hardware.cx -= 1;
-            }
-        }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
     };
+    let adjustment = match mnemonic {
+        AAA => Adjustment::Aaa,
+        DAA => Adjustment::Daa,
+        AAS => Adjustment::Aas,
+        DAS => Adjustment::Das,
+        AAM => Adjustment::Aam,
+        AAD => Adjustment::Aad,
+        _ => return Err(undecodable()),
+    };
+    let flag = hardware.read_flag_register();
+    let (ax, flags) = adjust(
+        adjustment,
+        hardware.read_from_word_register(AX),
+        base,
+        Flags::from(flag),
+    )
+    .ok_or_else(divide_error)?;
+    hardware.write_to_word_register(AX, ax);
+    hardware.write_flag_register(flags.merge(flag));
+    Ok(())
 }
 
-pub fn execute_jump_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // CALL / JMP (Direct within Segment)
-        0b11101000 | 0b11101001 => {
-            if binary_data.len() < 3 {
-                panic!("Instruction decode error");
-            }
-            let immediate = u16::from_le_bytes((&binary_data[1..3]).try_into().unwrap());
-            hardware.ip = match binary_data[2] & 0x80 {
-                0x0 => hardware.ip + 0x3 + immediate,
-                _ => ((hardware.ip as i16) + 3i16 - ((immediate as i16) * -1i16)) as u16,
-            };
+// SF, ZF, AF, PF and CF, the part LAHF / SAHF move through AH
+const FLAG_LOW_BYTE: u16 = 0x00d5;
+
+pub fn execute_flag_instruction(
+    mnemonic: Mnemonic,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let flag = hardware.read_flag_register();
+    match mnemonic {
+        LAHF => hardware.write_to_byte_register(AH, ((flag & FLAG_LOW_BYTE) | FLAG_RESERVED) as u8),
+        SAHF => {
+            let ah = u16::from(hardware.read_from_byte_register(AH));
+            hardware.write_flag_register((flag & !FLAG_LOW_BYTE) | (ah & FLAG_LOW_BYTE));
         }
-        // JMP (Direct within Segment-Short)
-        0b11101011 => {
-            if binary_data.len() < 2 {
-                panic!("Instruction decode error");
+        PUSHF => hardware.push_to_stack((flag & FLAG_MASK) | FLAG_RESERVED),
+        POPF => {
+            let value = hardware.pop_from_stack();
+            hardware.write_flag_register(value & FLAG_MASK);
+        }
+        CLC => hardware.write_flag(Flag::CF, false),
+        CMC => hardware.write_flag(Flag::CF, !hardware.read_flag(Flag::CF)),
+        STC => hardware.write_flag(Flag::CF, true),
+        CLD => hardware.write_flag(Flag::DF, false),
+        STD => hardware.write_flag(Flag::DF, true),
+        CLI => hardware.write_flag(Flag::IF, false),
+        STI => hardware.write_flag(Flag::IF, true),
+        _ => return Err(undecodable()),
+    }
+    Ok(())
+}
+
+pub fn execute_jump_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    match instruction {
+        // CALL / JMP (Direct within Segment / Direct within Segment-Short), the target is resolved
+        &Instruction::WithImmediate(mnemonic, Numerical::Imme(Immediate::UnsignedWord(target))) => {
+            if matches!(mnemonic, CALL) {
+                hardware.push_to_stack(hardware.ip);
             }
-            hardware.ip = match binary_data[1] & 0x80 {
-                0x0 => hardware.ip + 0x2 + (binary_data[1] as u16),
-                _ => hardware.ip + 0x2 - ((!(binary_data[1] - 0b1)) as u16),
-            };
+            hardware.ip = target;
         }
-        // CALL / JMP (Indirect within Segment / Indirect Intersegment)
-        0b11111111 if match_reg(binary_data[1], &[0b010, 0b011, 0b100, 0b101]) => {
-            if let (_, None, Some(r_m)) = Addressing::decode(0b1, &binary_data[1..], 0b11000111) {
-                match read_from_address(true, &r_m, hardware).expect("Unable to retrieve data") {
-                    Immediate::UnsignedWord(address) => hardware.ip = address,
-                    _ => unreachable!(),
-                };
-            } else {
-                panic!("Instruction decode error")
+        // CALL / JMP (Indirect within Segment)
+        &Instruction::WithAddress(mnemonic @ (CALL | JMP), r_m) => {
+            let address =
+                operand(&read_from_address(true, &r_m, hardware).ok_or_else(memory_fault)?);
+            if matches!(mnemonic, CALL) {
+                hardware.push_to_stack(hardware.ip);
             }
+            hardware.ip = address;
         }
-        // Direct Intersegment
-        0b10011010 | 0b11101010 => {
-            if let (_, Some(offset)) = decode_data(true, false, &binary_data[1..]) {
-                if let (_, Some(segment)) = decode_data(true, false, &binary_data[3..]) {
-                    hardware.ip = calculate_effective_address(
-                        &Addressing::DirectIndexAddressing(
-                            Numerical::Imme(offset),
-                            Numerical::Imme(segment),
-                        ),
-                        hardware,
+        // CALL / JMP (Direct Intersegment / Indirect Intersegment), offset then segment in memory
+        &Instruction::WithAddress(mnemonic @ (CALLFAR | JMPFAR), r_m) => {
+            let (segment, offset) = match r_m {
+                Addressing::DirectIndexAddressing(offset, segment) => {
+                    (numerical_value(&segment), numerical_value(&offset))
+                }
+                _ => {
+                    let address =
+                        calculate_effective_address(&r_m, hardware).ok_or_else(memory_fault)?;
+                    (
+                        hardware.read_word_from_memory(address + 2),
+                        hardware.read_word_from_memory(address),
                     )
-                    .expect("Unable to retrieve data");
-                } else {
-                    panic!("Instruction decode error")
                 }
-            } else {
-                panic!("Instruction decode error")
+            };
+            if matches!(mnemonic, CALLFAR) {
+                hardware.push_to_stack(hardware.cs);
+                hardware.push_to_stack(hardware.ip);
             }
+            (hardware.cs, hardware.ip) = (segment, offset);
         }
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute_return_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    match binary_data[0] {
-        // Within Segment
-        0b11000011 => {
-            hardware.ip = hardware.pop_from_stack().expect("Unable to retrieve data");
-        }
-        // Within Seg Adding Immed to SP
-        0b11000010 => {
-            let ip = hardware.pop_from_stack().expect("Unable to retrieve data");
-            let (_, disp) = decode_data(true, false, &binary_data[1..]);
-            hardware.ip = match disp.expect("Unable to retrieve data") {
-                Immediate::UnsignedWord(disp) => ((ip as i16) + (disp as i16)) as u16,
-                _ => unreachable!(),
-            };
+pub fn execute_return_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, disp) = match instruction {
+        // Within Segment / Intersegment
+        &Instruction::Standalone(mnemonic) => (mnemonic, 0x0000),
+        // Adding Immediate to SP
+        &Instruction::WithImmediate(mnemonic, Numerical::Imme(Immediate::UnsignedWord(disp))) => {
+            (mnemonic, disp)
         }
-        // Intersegment
-        0b11001011 => todo!("RETF"),
-        // Intersegment Adding Immediate to SP
-        0b11001010 => todo!("RETF"),
-        _ => panic!("Instruction decode error"),
+        _ => return Err(undecodable()),
+    };
+    hardware.ip = hardware.pop_from_stack();
+    if matches!(mnemonic, RETF) {
+        hardware.cs = hardware.pop_from_stack();
     }
+    hardware.sp = hardware.sp.wrapping_add(disp);
+    Ok(())
 }
 
-pub fn execute_conditional_jump_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    let zero_flag = hardware.clone().read_flags("ZF");
-    let sign_flag = hardware.clone().read_flags("SF");
-    let overflow_flag = hardware.clone().read_flags("OF");
-    let carry_flag = hardware.clone().read_flags("CF");
-    let jump_signal = match binary_data[0] {
-        0b01110100 => zero_flag,
-        0b01111100 => sign_flag != overflow_flag,
-        0b01111110 => zero_flag || (sign_flag != overflow_flag),
-        0b01110010 => carry_flag,
-        0b01110110 => carry_flag || zero_flag,
-        0b01111010 => todo!("JP"),
-        0b01110000 => todo!("JO"),
-        0b01111000 => todo!("JS"),
-        0b01110101 => !zero_flag,
-        0b01111101 => !carry_flag && !zero_flag,
-        0b01111111 => !zero_flag && (sign_flag == overflow_flag),
-        0b01110011 => !carry_flag,
-        0b01110111 => !carry_flag && !zero_flag,
-        0b01111011 => todo!("JNP"),
-        0b01110001 => todo!("JNO"),
-        0b01111001 => todo!("JNS"),
-        0b11100010 => {
-            hardware.cx -= 1;
+pub fn execute_conditional_jump_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (mnemonic, target) = match instruction {
+        &Instruction::WithImmediate(mnemonic, Numerical::Imme(Immediate::UnsignedWord(target))) => {
+            (mnemonic, target)
+        }
+        _ => return Err(undecodable()),
+    };
+    let zero_flag = hardware.read_flag(Flag::ZF);
+    let sign_flag = hardware.read_flag(Flag::SF);
+    let overflow_flag = hardware.read_flag(Flag::OF);
+    let carry_flag = hardware.read_flag(Flag::CF);
+    let parity_flag = hardware.read_flag(Flag::PF);
+    let jump_signal = match mnemonic {
+        JE => zero_flag,
+        JL => sign_flag != overflow_flag,
+        JLE => zero_flag || (sign_flag != overflow_flag),
+        JB => carry_flag,
+        JBE => carry_flag || zero_flag,
+        JP => parity_flag,
+        JO => overflow_flag,
+        JS => sign_flag,
+        JNE => !zero_flag,
+        JNL => sign_flag == overflow_flag,
+        JNLE => !zero_flag && (sign_flag == overflow_flag),
+        JNB => !carry_flag,
+        JNBE => !carry_flag && !zero_flag,
+        JNP => !parity_flag,
+        JNO => !overflow_flag,
+        JNS => !sign_flag,
+        // the LOOP family counts CX down without touching the flags
+        LOOP => {
+            hardware.cx = hardware.cx.wrapping_sub(1);
             hardware.cx != 0
         }
-        0b11100001 => todo!("LOOPZ"),
-        0b11100000 => todo!("LOOPNZ"),
-        0b11100011 => todo!("JCXZ"),
-        _ => panic!("Instruction decode error"),
+        LOOPZ => {
+            hardware.cx = hardware.cx.wrapping_sub(1);
+            hardware.cx != 0 && zero_flag
+        }
+        LOOPNZ => {
+            hardware.cx = hardware.cx.wrapping_sub(1);
+            hardware.cx != 0 && !zero_flag
+        }
+        JCXZ => hardware.cx == 0,
+        _ => return Err(undecodable()),
     };
-    let displacement = match binary_data[1] & 0x80 {
-        0b0 => (hardware.ip as i16) + 2i16 + (binary_data[1] as i16),
-        _ => (hardware.ip as i16) + 2i16 - (((binary_data[1] as i8) * -1i8) as i16),
-    } as u16;
     if jump_signal {
-        hardware.ip = displacement;
+        hardware.ip = target;
     }
+    Ok(())
 }
 
-pub fn execute_interrupt_instruction(binary_data: &[u8], hardware: &mut Hardware) {
-    hardware.push_to_stack(hardware.clone().read_flags("IF") as u16);
-    hardware.write_flags("IF", false);
-    match binary_data[0] {
+pub fn execute_interrupt_instruction(
+    instruction: &Instruction,
+    hardware: &mut Hardware,
+    minix: &mut Minix,
+    interrupts: &mut Interrupts,
+) -> Result<(), MmvmError> {
+    match instruction {
         // Type Specified
-        0b11001101 => match binary_data[1] {
-            0x20 => execute_systemcall(hardware),
-            _ => todo!(),
-        },
+        &Instruction::WithImmediate(INT, Numerical::Imme(Immediate::UnsignedByte(vector))) => {
+            interrupts.raise(vector, hardware, minix)?
+        }
         // Type 3
-        0b11001100 => todo!(),
-        _ => panic!("Instruction decode error"),
+        Instruction::Standalone(INT) => interrupts.raise(BREAKPOINT, hardware, minix)?,
+        Instruction::Standalone(INTO) => {
+            if hardware.read_flag(Flag::OF) {
+                interrupts.raise(OVERFLOW, hardware, minix)?
+            }
+        }
+        Instruction::Standalone(IRET) => Interrupts::iret(hardware),
+        _ => return Err(undecodable()),
     }
+    Ok(())
 }
 
-pub fn execute(binary_data: &[u8], hardware: &mut Hardware) {
-    if binary_data.is_empty() {
-        panic!("data length not enough");
-    }
-    match binary_data[0] {
-        0b11010111 => todo!("XLAT"),
-        0b10011111 => todo!("LAHF"),
-        0b10011110 => todo!("SAHF"),
-        0b10011100 => todo!("PUSHF"),
-        0b10011101 => todo!("POPF"),
-        0b00110111 => todo!("AAA"),
-        0b00100111 => todo!("BAA"),
-        0b00111111 => todo!("AAS"),
-        0b00101111 => todo!("DAS"),
-        0b10011000 => {
-            // CBW
-            let imme = hardware.clone().read_from_byte_register(AL);
+// Execute an instruction as decoded by Instruction::decode_with_size, word_mode is the w bit
+// that came with it
+pub fn execute(
+    instruction: &Instruction,
+    word_mode: bool,
+    hardware: &mut Hardware,
+    minix: &mut Minix,
+    interrupts: &mut Interrupts,
+    ports: &mut PortBus,
+) -> Result<(), MmvmError> {
+    let mnemonic = match instruction {
+        // segment override prefix, only lasts for the instruction it is attached to
+        Instruction::WithSegment(segment, instruction) => {
+            hardware.segment_override = Some(*segment);
+            let result = execute(instruction, word_mode, hardware, minix, interrupts, ports);
+            hardware.segment_override = None;
+            return result;
+        }
+        &Instruction::Standalone(mnemonic)
+        | &Instruction::WithInstruction(mnemonic, _)
+        | &Instruction::WithAddress(mnemonic, _)
+        | &Instruction::AddressToAddress(mnemonic, _, _, _)
+        | &Instruction::WithImmediate(mnemonic, _)
+        | &Instruction::ImmediateToAddress(mnemonic, _, _) => mnemonic,
+        Instruction::Undefined => return Err(undecodable()),
+    };
+    match mnemonic {
+        MOV | MOVBYTE => execute_move_instruction(instruction, hardware)?,
+        PUSH | POP => execute_push_pop_instruction(instruction, hardware)?,
+        XCHG => execute_exchange_instruction(instruction, hardware)?,
+        IN | OUT => execute_in_out_instruction(instruction, hardware, ports)?,
+        XLAT => {
+            let offset = hardware
+                .bx
+                .wrapping_add(u16::from(hardware.read_from_byte_register(AL)));
+            let address =
+                hardware.physical_address(hardware.segment_override.unwrap_or(DS), offset);
+            hardware.write_to_byte_register(AL, hardware.read_byte_from_memory(address));
+        }
+        LEA | LDS | LES => execute_load_instruction(instruction, hardware)?,
+        LAHF | SAHF | PUSHF | POPF | CLC | CMC | STC | CLD | STD | CLI | STI => {
+            execute_flag_instruction(mnemonic, hardware)?
+        }
+        ADD | ADC | SUB | SSB | CMP | CMPBYTE | AND | OR | XOR | TEST | TESTBYTE => {
+            execute_arithmic_instruction(instruction, word_mode, hardware)?
+        }
+        INC | DEC | NEG => execute_increase_decrease_instruction(instruction, word_mode, hardware)?,
+        AAA | DAA | AAS | DAS | AAM | AAD => execute_adjust_instruction(instruction, hardware)?,
+        MUL | IMUL | DIV | IDIV => {
+            execute_multiply_divide_instruction(instruction, word_mode, hardware)?
+        }
+        CBW => {
+            let imme = hardware.read_from_byte_register(AL);
             if (imme & 0x80) == 0x80 {
                 hardware.write_to_byte_register(AH, 0xff)
             } else {
                 hardware.write_to_byte_register(AH, 0x00)
             }
         }
-        0b10011001 => {
-            // CWD
-            let imme = hardware.clone().read_from_word_register(AX);
+        CWD => {
+            let imme = hardware.read_from_word_register(AX);
             if (imme & 0x8000) == 0x8000 {
                 hardware.write_to_word_register(DX, 0xffff)
             } else {
                 hardware.write_to_word_register(DX, 0x0000)
             }
         }
-        // RET (Within Segment / Intersegment)
-        0b11000011 | 0b11001011 => execute_return_instruction(binary_data, hardware),
-        // INT (Type 3)
-        0b11001100 => execute_interrupt_instruction(binary_data, hardware),
-        0b11001110 => todo!("INTO"),
-        0b11001111 => todo!("IRET"),
-        0b11111000 => todo!("CLC"),
-        0b11110101 => todo!("STC"),
-        0b11111001 => todo!("STC"),
-        0b11111100 => hardware.write_flags("DF", false),
-        0b11111101 => hardware.write_flags("DF", true),
-        0b11111010 => todo!("CLI"),
-        0b11111011 => todo!("STI"),
-        0b11110100 => todo!("HLT"),
-        0b10011011 => todo!("WAIT"),
-        0b11110000 => todo!("LOCK"),
-        0b11010101 if binary_data[1] == 0b00001010 => todo!("AAD"),
-        0b11010100 if binary_data[1] == 0b00001010 => todo!("AAM"),
-        // MOV (Register/Memory to Segment Register / Segment Register to Register/Memory)
-        0b10001110 | 0b10001100 if (binary_data[1] & 0b00100000) == 0b0 => {
-            execute_move_instruction(binary_data, hardware)
+        NOT => execute_not_instruction(instruction, word_mode, hardware)?,
+        SHL | SHR | SAR | ROL | ROR | RCL | RCR => {
+            execute_shift_instruction(instruction, word_mode, hardware)?
         }
-        // PUSH (Register/Memory), CALL / JMP (Indirect within Segment / Indirect Intersegment)
-        0b11111111
-            if match_reg(
-                binary_data[1],
-                &[0b000, 0b001, 0b010, 0b011, 0b100, 0b101, 0b110],
-            ) =>
-        {
-            match (binary_data[1] & 0b00111000) >> 3 {
-                0b110 => execute_push_pop_instruction(binary_data, hardware),
-                0b010 | 0b011 | 0b100 | 0b101 => execute_jump_instruction(binary_data, hardware),
-                0b000 | 0b001 => execute_increase_decrease_instruction(binary_data, hardware),
-                _ => panic!("Instruction decode error"),
-            }
-        }
-        // POP (Register/Memory)
-        0b10001111 if match_reg(binary_data[1], &[0b000]) => {
-            execute_push_pop_instruction(binary_data, hardware)
+        REP | REPE | REPNE => execute_repeat_instruction(instruction, hardware)?,
+        MOVSB | MOVSW | CMPSB | CMPSW | SCASB | SCASW | LODSB | LODSW | STOSB | STOSW => {
+            execute_string_instruction(mnemonic, hardware)?
         }
-        // LEA, LDS, LES
-        0b10001101 | 0b11000101 | 0b11000100 => execute_load_instruction(binary_data, hardware),
-        // CALL (Direct within Segment / Direct Intersegment), JMP (Direct within Segment-Short / Direct within Segment / Direct Intersegment)
-        0b11101000 | 0b10011010 | 0b11101001 | 0b11101011 | 0b11101010 => {
-            execute_jump_instruction(binary_data, hardware)
+        CALL | CALLFAR | JMP | JMPFAR | JMPSHORT => {
+            execute_jump_instruction(instruction, hardware)?
         }
-        // RET (Within Segment Adding Immediate to SP / Intersegment Adding Immediate to SP)
-        0b11000010 | 0b11001010 => execute_return_instruction(binary_data, hardware),
-        // conditional jump and loop
-        0b01110000..=0b01111111 | 0b11100000..=0b11100011 => {
-            execute_conditional_jump_instruction(binary_data, hardware)
+        RET | RETF => execute_return_instruction(instruction, hardware)?,
+        JE | JL | JLE | JB | JBE | JP | JO | JS | JNE | JNL | JNLE | JNB | JNBE | JNP | JNO
+        | JNS | LOOP | LOOPZ | LOOPNZ | JCXZ => {
+            execute_conditional_jump_instruction(instruction, hardware)?
         }
-        // INT (Type Specified)
-        0b11001101 => execute_interrupt_instruction(binary_data, hardware),
-        // MOV (Immediate to Register/Memory)
-        0b11000110 | 0b11000111 if match_reg(binary_data[1], &[0b000]) => {
-            execute_move_instruction(binary_data, hardware)
+        INT | INTO | IRET => {
+            execute_interrupt_instruction(instruction, hardware, minix, interrupts)?
         }
-        // MOV (Register/Memory to/from Register, Immediate to Register, Memory <-> Accumulator )
-        0b10001000..=0b10001011
-        | 0b10110000..=0b10110111
-        | 0b10111000..=0b10111111
-        | 0b10100000
-        | 0b10100001
-        | 0b10100010
-        | 0b10100011 => execute_move_instruction(binary_data, hardware),
-        // PUSH / POP (Register, Segment Register)
-        0b01010000..=0b01010111
-        | 0b00000110
-        | 0b00001110
-        | 0b00010110
-        | 0b00011110
-        | 0b01011000..=0b01011111
-        | 0b00000111
-        | 0b00001111
-        | 0b00010111
-        | 0b00011111 => execute_push_pop_instruction(binary_data, hardware),
-        // XCHG
-        0b10000110 | 0b10000111 | 0b10010000..=0b10010111 => {
-            execute_exchange_instruction(binary_data, hardware)
+        HLT => hardware.halted = true,
+        WAIT | ESC | LOCK => return Err(unimplemented()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::instruction::Instruction;
+    use crate::disassembler::mnemonic::Mnemonic::{LODSB, MOVSW};
+    use crate::disassembler::numerical::{Immediate, Numerical};
+    use crate::disassembler::register::ByteRegister::AH;
+    use crate::disassembler::register::WordRegister::{AX, BX, DX};
+    use crate::error::MmvmError;
+    use crate::interpreter::execution::{
+        execute, execute_conditional_jump_instruction, execute_multiply_divide_instruction,
+        execute_repeat_instruction, execute_string_instruction,
+    };
+    use crate::interpreter::hardware::{Flag, Hardware};
+    use crate::interpreter::interrupt::Interrupts;
+    use crate::interpreter::port::{Device, PortBus};
+    use crate::interpreter::systemcall::Minix;
+
+    // decode the way the interpreter does, then execute what came out
+    fn step(
+        binary_data: &[u8],
+        hardware: &mut Hardware,
+        minix: &mut Minix,
+        interrupts: &mut Interrupts,
+        ports: &mut PortBus,
+    ) -> Result<(), MmvmError> {
+        let (_, instruction, word_mode) = Instruction::decode_with_size(hardware.ip, binary_data)?;
+        execute(&instruction, word_mode, hardware, minix, interrupts, ports)
+    }
+
+    // one instruction on a machine without host handlers or devices, vectors come from the IVT
+    fn run(binary_data: &[u8], hardware: &mut Hardware) -> Result<(), MmvmError> {
+        step(
+            binary_data,
+            hardware,
+            &mut Minix::new(),
+            &mut Interrupts::new(),
+            &mut PortBus::new(),
+        )
+    }
+
+    fn multiply_divide(
+        binary_data: &[u8],
+        ax: u16,
+        dx: u16,
+        bx: u16,
+    ) -> Result<Hardware, MmvmError> {
+        let mut hardware = Hardware::new();
+        hardware.write_to_word_register(AX, ax);
+        hardware.write_to_word_register(DX, dx);
+        hardware.write_to_word_register(BX, bx);
+        let (_, instruction, word_mode) = Instruction::decode_with_size(0x0000, binary_data)?;
+        execute_multiply_divide_instruction(&instruction, word_mode, &mut hardware)?;
+        Ok(hardware)
+    }
+
+    #[test]
+    fn test_multiply_divide() {
+        // (bytes, ax, dx, bx, expected ax, expected dx, CF and OF)
+        let testcases = [
+            (
+                vec![0xf6, 0xe3],
+                0x0010,
+                0x0000,
+                0x0010,
+                0x0100,
+                0x0000,
+                true,
+            ),
+            (
+                vec![0xf7, 0xe3],
+                0xffff,
+                0x0000,
+                0x0002,
+                0xfffe,
+                0x0001,
+                true,
+            ),
+            (
+                vec![0xf6, 0xeb],
+                0x00ff,
+                0x0000,
+                0x0002,
+                0xfffe,
+                0x0000,
+                false,
+            ),
+            (
+                vec![0xf6, 0xeb],
+                0x0040,
+                0x0000,
+                0x0002,
+                0x0080,
+                0x0000,
+                true,
+            ),
+            (
+                vec![0xf7, 0xeb],
+                0xffff,
+                0x1234,
+                0xffff,
+                0x0001,
+                0x0000,
+                false,
+            ),
+            (
+                vec![0xf7, 0xeb],
+                0x4000,
+                0x0000,
+                0x0004,
+                0x0000,
+                0x0001,
+                true,
+            ),
+            (
+                vec![0xf6, 0xf3],
+                0x0107,
+                0x0000,
+                0x0002,
+                0x0183,
+                0x0000,
+                false,
+            ),
+            (
+                vec![0xf7, 0xf3],
+                0x0007,
+                0x0001,
+                0x0002,
+                0x8003,
+                0x0001,
+                false,
+            ),
+            (
+                vec![0xf6, 0xfb],
+                0xfff9,
+                0x0000,
+                0x0002,
+                0xfffd,
+                0x0000,
+                false,
+            ),
+            (
+                vec![0xf7, 0xfb],
+                0xfff9,
+                0xffff,
+                0xfffe,
+                0x0003,
+                0xffff,
+                false,
+            ),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let hardware =
+                multiply_divide(&testcase.0, testcase.1, testcase.2, testcase.3).unwrap();
+            assert_eq!(
+                (
+                    hardware.read_from_word_register(AX),
+                    hardware.read_from_word_register(DX),
+                ),
+                (testcase.4, testcase.5),
+                "#{}",
+                i
+            );
+            if testcase.0[1] & 0b00110000 == 0b00100000 {
+                assert_eq!(hardware.read_flag(Flag::CF), testcase.6, "#{}", i);
+                assert_eq!(hardware.read_flag(Flag::OF), testcase.6, "#{}", i);
+            }
         }
-        // IN / OUT
-        0b11100100 | 0b11100101 | 0b11101100 | 0b11101101 | 0b11100110 | 0b11100111
-        | 0b11101110 | 0b11101111 => todo!("IN, OUT"),
-        // ADD / ADC / SUB / SSB / CMP (Reg./Memory with Register to Either)
-        0b00000000..=0b00000011
-        | 0b00010000..=0b00010011
-        | 0b00101000..=0b00101011
-        | 0b00011000..=0b00011011
-        | 0b00111000..=0b00111011 => execute_arithmic_instruction(binary_data, hardware),
-        // ADD / ADC / SUB / SSB / CMP (Immediate from Register/Memory)
-        0b10000000..=0b10000011
-            if match_reg(binary_data[1], &[0b000, 0b010, 0b011, 0b101, 0b111]) =>
-        {
-            execute_arithmic_instruction(binary_data, hardware)
+    }
+
+    #[test]
+    fn test_divide_error() {
+        // zero divisor
+        assert!(matches!(
+            multiply_divide(&[0xf7, 0xf3], 0x0001, 0x0000, 0x0000),
+            Err(MmvmError::DivideError { .. })
+        ));
+        // quotient wider than AX
+        assert!(matches!(
+            multiply_divide(&[0xf7, 0xf3], 0x0000, 0x0002, 0x0002),
+            Err(MmvmError::DivideError { .. })
+        ));
+        // -128 does not fit an 8086 IDIV quotient
+        assert!(multiply_divide(&[0xf6, 0xfb], 0xff00, 0x0000, 0x0002).is_err());
+        assert!(multiply_divide(&[0xf7, 0xfb], 0x0000, 0x8000, 0x0001).is_err());
+    }
+
+    fn repeat(binary_data: &[u8], hardware: &mut Hardware) {
+        let (_, instruction) = Instruction::decode(0x0000, binary_data).unwrap();
+        execute_repeat_instruction(&instruction, hardware).unwrap();
+    }
+
+    #[test]
+    fn test_string_instruction() {
+        let mut hardware = Hardware::new();
+        for (i, &byte) in b"hello\0".iter().enumerate() {
+            hardware.write_byte_to_memory(0x0100 + i as u32, byte);
         }
-        // ADD / ADC / SUB / SSB / CMP (Immediate to Accumulator)
-        0b00000100 | 0b00000101 | 0b00010100 | 0b00010101 | 0b00101100 | 0b00101101
-        | 0b00011100 | 0b00011101 | 0b00111100 | 0b00111101 => {
-            execute_arithmic_instruction(binary_data, hardware)
+        // strlen: repne scasb stops right after the terminator
+        hardware.di = 0x0100;
+        hardware.cx = 0xffff;
+        hardware.write_to_word_register(AX, 0x0000);
+        repeat(&[0xf2, 0xae], &mut hardware);
+        assert_eq!((hardware.di, hardware.cx), (0x0106, 0xfff9));
+        assert!(hardware.read_flag(Flag::ZF));
+
+        // strcmp: repe cmpsb stops at the first difference
+        for (i, &byte) in b"help\0".iter().enumerate() {
+            hardware.write_byte_to_memory(0x0200 + i as u32, byte);
         }
-        // INC / DEC (Register/Memory)
-        0b11111110 if match_reg(binary_data[1], &[0b000, 0b001]) => {
-            execute_increase_decrease_instruction(binary_data, hardware)
+        (hardware.si, hardware.di, hardware.cx) = (0x0100, 0x0200, 6);
+        repeat(&[0xf3, 0xa6], &mut hardware);
+        assert_eq!((hardware.si, hardware.di, hardware.cx), (0x0104, 0x0204, 2));
+        assert!(!hardware.read_flag(Flag::ZF));
+        assert!(hardware.read_flag(Flag::CF));
+
+        // memset: rep stosw ignores ZF and walks down with DF set
+        hardware.write_flag(Flag::DF, true);
+        hardware.write_flag(Flag::ZF, false);
+        (hardware.di, hardware.cx) = (0x0304, 3);
+        hardware.write_to_word_register(AX, 0xbeef);
+        repeat(&[0xf3, 0xab], &mut hardware);
+        assert_eq!((hardware.di, hardware.cx), (0x02fe, 0));
+        assert_eq!(hardware.read_word_from_memory(0x0300), 0xbeef);
+        assert_eq!(hardware.read_word_from_memory(0x0304), 0xbeef);
+
+        // lodsb with DF clear
+        hardware.write_flag(Flag::DF, false);
+        (hardware.si, hardware.cx) = (0x0101, 0);
+        repeat(&[0xf3, 0xac], &mut hardware);
+        assert_eq!(hardware.si, 0x0101);
+        execute_string_instruction(LODSB, &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.read_from_word_register(AX), hardware.si),
+            (0xbe65, 0x0102)
+        );
+        (hardware.si, hardware.di) = (0x0300, 0x0400);
+        execute_string_instruction(MOVSW, &mut hardware).unwrap();
+        assert_eq!(hardware.read_word_from_memory(0x0400), 0xbeef);
+    }
+
+    #[test]
+    fn test_segment_override() {
+        let mut hardware = Hardware::new();
+        (hardware.ds, hardware.ss, hardware.es) = (0x1000, 0x2000, 0x3000);
+        (hardware.bx, hardware.bp) = (0x0010, 0x0010);
+        hardware.write_word_to_memory(0x10010, 0x1111);
+        hardware.write_word_to_memory(0x20010, 0x2222);
+        hardware.write_word_to_memory(0x30010, 0x3333);
+        let testcases = [
+            // mov ax, [bx] / mov ax, [bp+0] / mov ax, es:[bx] / mov ax, ds:[bp+0]
+            (vec![0x8b, 0x07], 0x1111),
+            (vec![0x8b, 0x46, 0x00], 0x2222),
+            (vec![0x26, 0x8b, 0x07], 0x3333),
+            (vec![0x3e, 0x8b, 0x46, 0x00], 0x1111),
+        ];
+        for (i, (bytes, expected)) in testcases.into_iter().enumerate() {
+            run(&bytes, &mut hardware).unwrap();
+            assert_eq!(hardware.read_from_word_register(AX), expected, "#{}", i);
+            assert!(hardware.segment_override.is_none(), "#{}", i);
         }
-        // INC / DEC (Register)
-        0b01000000..=0b01000111 | 0b01001000..=0b01001111 => {
-            execute_increase_decrease_instruction(binary_data, hardware)
+
+        // es: rep movsb reads ES:SI instead of DS:SI with the prefix on either side of REP
+        for (i, bytes) in [[0x26, 0xf3, 0xa4], [0xf3, 0x26, 0xa4]].iter().enumerate() {
+            (hardware.si, hardware.di, hardware.cx) = (0x0010, 0x0020, 2);
+            hardware.write_word_to_memory(0x30020, 0x0000);
+            run(bytes, &mut hardware).unwrap();
+            assert_eq!(hardware.read_word_from_memory(0x30020), 0x3333, "#{}", i);
+            assert_eq!(hardware.cx, 0, "#{}", i);
+            assert!(hardware.segment_override.is_none(), "#{}", i);
         }
-        0b11110110 | 0b11110111
-            if match_reg(
-                binary_data[1],
-                &[0b000, 0b010, 0b011, 0b100, 0b101, 0b110, 0b111],
-            ) =>
-        {
-            match (binary_data[1] & 0b00111000) >> 3 {
-                // NEG
-                0b011 => execute_negation_instruction(binary_data, hardware),
-                // MUL, IMUL, DIV, IDIV
-                0b100 | 0b101 | 0b110 | 0b111 => {
-                    execute_multiply_divide_instruction(binary_data, hardware)
-                }
-                // NOT
-                0b010 => execute_not_instruction(binary_data, hardware),
-                // TEST (Immediate Data and Register/Memory)
-                0b000 => execute_test_instruction(binary_data, hardware),
-                _ => panic!("Instruction decode error"),
+    }
+
+    #[test]
+    fn test_flag_instruction() {
+        let mut hardware = Hardware::new();
+        hardware.sp = 0x0100;
+        // STC, CMC, CMC
+        run(&[0xf9], &mut hardware).unwrap();
+        assert!(hardware.read_flag(Flag::CF));
+        run(&[0xf5], &mut hardware).unwrap();
+        assert!(!hardware.read_flag(Flag::CF));
+        run(&[0xf5], &mut hardware).unwrap();
+        assert!(hardware.read_flag(Flag::CF));
+        // STI, STD, then PUSHF shows the reserved bits
+        run(&[0xfb], &mut hardware).unwrap();
+        run(&[0xfd], &mut hardware).unwrap();
+        run(&[0x9c], &mut hardware).unwrap();
+        assert_eq!(hardware.read_word_from_memory(0x00fe), 0xf603);
+        // CLC, CLI, CLD, then POPF restores them
+        run(&[0xf8], &mut hardware).unwrap();
+        run(&[0xfa], &mut hardware).unwrap();
+        run(&[0xfc], &mut hardware).unwrap();
+        assert_eq!(hardware.read_flag_register(), 0x0000);
+        run(&[0x9d], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.read_flag_register(), hardware.sp),
+            (0x0601, 0x0100)
+        );
+        // POPF drops the reserved bits of whatever it pops
+        hardware.push_to_stack(0xffff);
+        run(&[0x9d], &mut hardware).unwrap();
+        assert_eq!(hardware.read_flag_register(), 0x0fd5);
+        // LAHF / SAHF only move SF, ZF, AF, PF and CF
+        run(&[0x9f], &mut hardware).unwrap();
+        assert_eq!(hardware.read_from_byte_register(AH), 0xd7);
+        hardware.write_to_byte_register(AH, 0x40);
+        run(&[0x9e], &mut hardware).unwrap();
+        assert_eq!(hardware.read_flag_register(), 0x0f40);
+    }
+
+    #[test]
+    fn test_far_transfer() {
+        let mut hardware = Hardware::new();
+        (hardware.cs, hardware.ip, hardware.sp) = (0x0100, 0x0020, 0x0200);
+        // call far 1234:5678
+        run(&[0x9a, 0x78, 0x56, 0x34, 0x12], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x1234, 0x5678, 0x01fc)
+        );
+        assert_eq!(hardware.read_word_from_memory(0x01fc), 0x0020);
+        assert_eq!(hardware.read_word_from_memory(0x01fe), 0x0100);
+        // retf 0002 comes back and drops an argument
+        run(&[0xca, 0x02, 0x00], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x0100, 0x0020, 0x0202)
+        );
+
+        // call far [bx] takes offset and segment from memory
+        hardware.bx = 0x0040;
+        hardware.write_word_to_memory(0x0040, 0x0008);
+        hardware.write_word_to_memory(0x0042, 0x2000);
+        run(&[0xff, 0x1f], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x2000, 0x0008, 0x01fe)
+        );
+        run(&[0xcb], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x0100, 0x0020, 0x0202)
+        );
+
+        // jmp far leaves the stack alone
+        run(&[0xea, 0x00, 0x00, 0xff, 0xff], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0xffff, 0x0000, 0x0202)
+        );
+        run(&[0xff, 0x2f], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x2000, 0x0008, 0x0202)
+        );
+    }
+
+    #[test]
+    fn test_conditional_jump() {
+        const CF: u16 = 0x0001;
+        const PF: u16 = 0x0004;
+        const ZF: u16 = 0x0040;
+        const SF: u16 = 0x0080;
+        const OF: u16 = 0x0800;
+        // opcode, flags, cx before, taken, cx after
+        let testcases: [(u8, u16, u16, bool, u16); 50] = [
+            (0x70, OF, 0, true, 0), // jo
+            (0x70, 0, 0, false, 0),
+            (0x71, 0, 0, true, 0), // jno
+            (0x71, OF, 0, false, 0),
+            (0x72, CF, 0, true, 0), // jb
+            (0x72, 0, 0, false, 0),
+            (0x73, 0, 0, true, 0), // jnb
+            (0x73, CF, 0, false, 0),
+            (0x74, ZF, 0, true, 0), // je
+            (0x74, 0, 0, false, 0),
+            (0x75, 0, 0, true, 0), // jne
+            (0x75, ZF, 0, false, 0),
+            (0x76, CF, 0, true, 0), // jbe
+            (0x76, ZF, 0, true, 0),
+            (0x76, 0, 0, false, 0),
+            (0x77, 0, 0, true, 0), // jnbe
+            (0x77, CF, 0, false, 0),
+            (0x77, ZF, 0, false, 0),
+            (0x78, SF, 0, true, 0), // js
+            (0x78, 0, 0, false, 0),
+            (0x79, 0, 0, true, 0), // jns
+            (0x79, SF, 0, false, 0),
+            (0x7a, PF, 0, true, 0), // jp
+            (0x7a, 0, 0, false, 0),
+            (0x7b, 0, 0, true, 0), // jnp
+            (0x7b, PF, 0, false, 0),
+            (0x7c, SF, 0, true, 0), // jl
+            (0x7c, OF, 0, true, 0),
+            (0x7c, SF | OF, 0, false, 0),
+            (0x7d, SF | OF, 0, true, 0), // jnl
+            (0x7d, SF, 0, false, 0),
+            (0x7e, ZF | SF | OF, 0, true, 0), // jle
+            (0x7e, OF, 0, true, 0),
+            (0x7e, SF | OF, 0, false, 0),
+            (0x7f, SF | OF, 0, true, 0), // jnle
+            (0x7f, ZF, 0, false, 0),
+            (0x7f, SF, 0, false, 0),
+            (0xe0, 0, 2, true, 1), // loopnz
+            (0xe0, ZF, 2, false, 1),
+            (0xe0, 0, 1, false, 0),
+            (0xe1, ZF, 2, true, 1), // loopz
+            (0xe1, 0, 2, false, 1),
+            (0xe1, ZF, 1, false, 0),
+            (0xe2, 0, 2, true, 1), // loop
+            (0xe2, ZF, 1, false, 0),
+            (0xe2, 0, 0, true, 0xffff),
+            (0xe3, 0, 0, true, 0), // jcxz
+            (0xe3, 0, 1, false, 1),
+            (0xe3, ZF, 0, true, 0),
+            (0xe3, ZF, 0xffff, false, 0xffff),
+        ];
+        for (opcode, flags, cx, taken, count) in testcases {
+            for displacement in [0x10, 0xf0] {
+                let binary_data = [opcode, displacement];
+                let (length, instruction) = Instruction::decode(0x0100, &binary_data).unwrap();
+                let target = match instruction {
+                    Instruction::WithImmediate(
+                        _,
+                        Numerical::Imme(Immediate::UnsignedWord(target)),
+                    ) => target,
+                    _ => panic!("{:02x} does not decode to a jump", opcode),
+                };
+                let mut hardware = Hardware::new();
+                (hardware.ip, hardware.cx) = (0x0100 + length as u16, cx);
+                hardware.write_flag_register(flags);
+                execute_conditional_jump_instruction(&instruction, &mut hardware).unwrap();
+                let expected = if taken { target } else { 0x0102 };
+                assert_eq!(
+                    (hardware.ip, hardware.cx, hardware.read_flag_register()),
+                    (expected, count, flags),
+                    "{:02x} {:02x} with flags {:04x} and cx {:04x}",
+                    opcode,
+                    displacement,
+                    flags,
+                    cx
+                );
             }
         }
-        // shift
-        0b11010000..=0b11010011
-            if match_reg(
-                binary_data[1],
-                &[0b000, 0b001, 0b010, 0b011, 0b100, 0b101, 0b111],
-            ) =>
-        {
-            execute_shift_instruction(binary_data, hardware)
+    }
+
+    #[test]
+    fn test_interrupt_instruction() {
+        let mut hardware = Hardware::new();
+        (hardware.cs, hardware.ip, hardware.sp) = (0x1000, 0x0002, 0x0100);
+        // vector 4 at 0000:0010, vector 0x80 at 0000:0200
+        hardware.write_word_to_memory(0x0010, 0x0100);
+        hardware.write_word_to_memory(0x0012, 0x3000);
+        hardware.write_word_to_memory(0x0200, 0x0400);
+        hardware.write_word_to_memory(0x0202, 0x4000);
+
+        // INTO does nothing while OF is clear
+        run(&[0xce], &mut hardware).unwrap();
+        assert_eq!((hardware.cs, hardware.ip), (0x1000, 0x0002));
+        hardware.write_flag(Flag::OF, true);
+        hardware.write_flag(Flag::IF, true);
+        run(&[0xce], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x3000, 0x0100, 0x00fa)
+        );
+        assert!(!hardware.read_flag(Flag::IF));
+        run(&[0xcf], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x1000, 0x0002, 0x0100)
+        );
+        assert!(hardware.read_flag(Flag::IF));
+
+        run(&[0xcd, 0x80], &mut hardware).unwrap();
+        assert_eq!((hardware.cs, hardware.ip), (0x4000, 0x0400));
+        assert!(matches!(
+            run(&[0xcc], &mut hardware),
+            Err(MmvmError::UnhandledInterrupt { vector: 0x03, .. })
+        ));
+    }
+
+    #[test]
+    fn test_adjust_auxiliary_carry() {
+        let mut hardware = Hardware::new();
+        // (opcode, ax, af, expected ax, af, cf)
+        let testcases = [
+            (0x37, 0x000b, false, 0x0101, true, true),
+            (0x37, 0x0002, true, 0x0108, true, true),
+            (0x37, 0x0005, false, 0x0005, false, false),
+            (0x27, 0x0011, true, 0x0017, true, false),
+            (0x27, 0x0012, false, 0x0012, false, false),
+            (0x2f, 0x000b, false, 0x0005, true, false),
+            (0x3f, 0x0102, true, 0x000c, true, true),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            hardware.ax = testcase.1;
+            hardware.write_flag(Flag::AF, testcase.2);
+            hardware.write_flag(Flag::CF, false);
+            run(&[testcase.0], &mut hardware).unwrap();
+            assert_eq!(
+                (
+                    hardware.ax,
+                    hardware.read_flag(Flag::AF),
+                    hardware.read_flag(Flag::CF)
+                ),
+                (testcase.3, testcase.4, testcase.5),
+                "#{}",
+                i
+            );
         }
-        // AND / OR / XOR (Reg./Memory and Register to Either, Immediate to Accumulator )
-        0b00100000..=0b00100011
-        | 0b00100100
-        | 0b00100101
-        | 0b00001000..=0b00001011
-        | 0b00001100
-        | 0b00001101
-        | 0b00110000..=0b00110011
-        | 0b00110100
-        | 0b00110101 => execute_logic_instruction(binary_data, hardware),
-        // AND / OR / XOR (Immediate to Register/Memory)
-        0b10000000 | 0b10000001 if match_reg(binary_data[1], &[0b001, 0b100, 0b110]) => {
-            execute_logic_instruction(binary_data, hardware)
+    }
+
+    #[test]
+    fn test_load_far_pointer_and_translate() {
+        let mut hardware = Hardware::new();
+        (hardware.ds, hardware.es, hardware.bx) = (0x0100, 0x0200, 0x0010);
+        hardware.write_word_to_memory(0x01010, 0x5678);
+        hardware.write_word_to_memory(0x01012, 0x1234);
+        // les di, [bx] / lds si, [bx]
+        run(&[0xc4, 0x3f], &mut hardware).unwrap();
+        assert_eq!(
+            (hardware.di, hardware.es, hardware.ds),
+            (0x5678, 0x1234, 0x0100)
+        );
+        run(&[0xc5, 0x37], &mut hardware).unwrap();
+        assert_eq!((hardware.si, hardware.ds), (0x5678, 0x1234));
+
+        // xlat reads DS:BX+AL, or the override segment
+        (hardware.ds, hardware.es, hardware.bx) = (0x0100, 0x0200, 0x0020);
+        hardware.write_byte_to_memory(0x01025, 0x41);
+        hardware.write_byte_to_memory(0x02025, 0x61);
+        hardware.write_to_word_register(AX, 0x0005);
+        run(&[0xd7], &mut hardware).unwrap();
+        assert_eq!(hardware.read_from_word_register(AX), 0x0041);
+        hardware.write_to_word_register(AX, 0x0005);
+        run(&[0x26, 0xd7], &mut hardware).unwrap();
+        assert_eq!(hardware.read_from_word_register(AX), 0x0061);
+    }
+
+    #[test]
+    fn test_in_out_instruction() {
+        struct Latch(u16);
+
+        impl Device for Latch {
+            fn read_byte(&mut self, port: u16) -> u8 {
+                (self.0 >> ((port & 0b1) * 8)) as u8
+            }
+
+            fn write_byte(&mut self, port: u16, value: u8) {
+                self.0 = match port & 0b1 {
+                    0b0 => (self.0 & 0xff00) | u16::from(value),
+                    _ => (self.0 & 0x00ff) | (u16::from(value) << 8),
+                };
+            }
         }
-        // TEST (Register/Memory and Register, Immediate Data and Accumulator)
-        0b10000100 | 0b10000101 | 0b10101000 | 0b10101001 => {
-            execute_test_instruction(binary_data, hardware)
+
+        let mut hardware = Hardware::new();
+        let mut ports = PortBus::new();
+        ports.attach(0x0040..=0x0041, Box::new(Latch(0x0000)));
+        // (bytes, ax, dx, expected ax), the latch keeps what the earlier rows wrote
+        let testcases = [
+            // out 40, ax / in al, 41
+            (&[0xe7, 0x40][..], 0xbeef, 0x0000, 0xbeef),
+            (&[0xe4, 0x41][..], 0xbeef, 0x0000, 0xbebe),
+            // out dx, al / in ax, dx
+            (&[0xee][..], 0x0012, 0x0041, 0x0012),
+            (&[0xed][..], 0x0012, 0x0040, 0x12ef),
+            // nothing on port 0x0060
+            (&[0xe4, 0x60][..], 0x12ef, 0x0000, 0x12ff),
+            (&[0xed][..], 0x12ff, 0x0060, 0xffff),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            (hardware.ax, hardware.dx) = (testcase.1, testcase.2);
+            step(
+                testcase.0,
+                &mut hardware,
+                &mut Minix::new(),
+                &mut Interrupts::new(),
+                &mut ports,
+            )
+            .unwrap();
+            assert_eq!(hardware.ax, testcase.3, "#{}", i);
         }
-        // REP
-        0b11110010 | 0b11110011 => execute_repeat_instruction(binary_data, hardware),
-        // ESC
-        0b11011000 | 0b11011111 => todo!("ESC"),
-        _ => panic!("Instruction decode error"),
     }
 }
@@ -1,10 +1,42 @@
-use std::{
-    collections::HashMap,
-    fmt::{Display, Formatter},
-};
+use std::fmt::{Display, Formatter};
 
 use crate::disassembler::register::{ByteRegister, SegmentRegister, WordRegister};
 
+// 8086 has a 20-bit physical address bus
+pub const MEMORY_SIZE: usize = 0x100000;
+// FLAGS as the 8086 stores it: bits 1 and 12-15 always read as 1, bits 3 and 5 as 0
+pub const FLAG_MASK: u16 = 0x0fd5;
+pub const FLAG_RESERVED: u16 = 0xf002;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    CF,
+    PF,
+    AF,
+    ZF,
+    SF,
+    TF,
+    IF,
+    DF,
+    OF,
+}
+
+impl Flag {
+    pub const fn mask(self) -> u16 {
+        match self {
+            Self::CF => 1 << 0,
+            Self::PF => 1 << 2,
+            Self::AF => 1 << 4,
+            Self::ZF => 1 << 6,
+            Self::SF => 1 << 7,
+            Self::TF => 1 << 8,
+            Self::IF => 1 << 9,
+            Self::DF => 1 << 10,
+            Self::OF => 1 << 11,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Hardware {
     pub ax: u16,
@@ -21,9 +53,19 @@ pub struct Hardware {
     pub ds: u16,
     pub ip: u16,
     flag: u16,
-    stack: Vec<u8>,
-    memory: HashMap<u16, u8>,
-    pub systemcall_count: usize,
+    memory: Vec<u8>,
+    // bytes holding a cached decoded instruction, and the writes that landed on them since the
+    // interpreter last looked
+    code: Vec<bool>,
+    code_writes: Vec<u32>,
+    pub halted: bool,
+    // program break, the end of bss it may not shrink below and the size of the segment it may
+    // grow into, as offsets from DS
+    pub brk: u16,
+    pub brk_start: u16,
+    pub segment_size: u32,
+    // set by a segment override prefix for the one instruction that follows it
+    pub segment_override: Option<SegmentRegister>,
 }
 
 impl Hardware {
@@ -43,9 +85,14 @@ impl Hardware {
             ds: 0x0000,
             ip: 0x0000,
             flag: 0x0000,
-            stack: Vec::new(),
-            memory: HashMap::new(),
-            systemcall_count: 0,
+            memory: vec![0x00; MEMORY_SIZE],
+            code: vec![false; MEMORY_SIZE],
+            code_writes: Vec::new(),
+            halted: false,
+            brk: 0x0000,
+            brk_start: 0x0000,
+            segment_size: 0x10000,
+            segment_override: None,
         }
     }
 
@@ -62,7 +109,7 @@ impl Hardware {
         }
     }
 
-    pub fn read_from_word_register(self, reg: WordRegister) -> u16 {
+    pub fn read_from_word_register(&self, reg: WordRegister) -> u16 {
         match reg {
             WordRegister::AX => self.ax,
             WordRegister::CX => self.cx,
@@ -88,16 +135,16 @@ impl Hardware {
         }
     }
 
-    pub fn read_from_byte_register(self, reg: ByteRegister) -> u8 {
+    pub fn read_from_byte_register(&self, reg: ByteRegister) -> u8 {
         match reg {
             ByteRegister::AL => (self.ax & 0x00ff) as u8,
             ByteRegister::CL => (self.cx & 0x00ff) as u8,
             ByteRegister::DL => (self.dx & 0x00ff) as u8,
             ByteRegister::BL => (self.bx & 0x00ff) as u8,
-            ByteRegister::AH => (self.ax & 0xff00) as u8,
-            ByteRegister::CH => (self.cx & 0xff00) as u8,
-            ByteRegister::DH => (self.dx & 0xff00) as u8,
-            ByteRegister::BH => (self.bx & 0xff00) as u8,
+            ByteRegister::AH => (self.ax >> 8) as u8,
+            ByteRegister::CH => (self.cx >> 8) as u8,
+            ByteRegister::DH => (self.dx >> 8) as u8,
+            ByteRegister::BH => (self.bx >> 8) as u8,
         }
     }
 
@@ -110,7 +157,7 @@ impl Hardware {
         }
     }
 
-    pub fn read_from_segment_register(self, reg: SegmentRegister) -> u16 {
+    pub fn read_from_segment_register(&self, reg: SegmentRegister) -> u16 {
         match reg {
             SegmentRegister::ES => self.es,
             SegmentRegister::CS => self.cs,
@@ -119,110 +166,92 @@ impl Hardware {
         }
     }
 
+    pub fn physical_address(&self, reg: SegmentRegister, offset: u16) -> u32 {
+        let segment = match reg {
+            SegmentRegister::ES => self.es,
+            SegmentRegister::CS => self.cs,
+            SegmentRegister::SS => self.ss,
+            SegmentRegister::DS => self.ds,
+        };
+        ((u32::from(segment) << 4) + u32::from(offset)) & (MEMORY_SIZE as u32 - 1)
+    }
+
     pub fn push_to_stack(&mut self, value: u16) {
-        if self.stack.is_empty() {
-            self.sp = 0x0001;
-        } else {
-            self.sp += 0x0002;
+        self.sp = self.sp.wrapping_sub(0x0002);
+        let addr = self.physical_address(SegmentRegister::SS, self.sp);
+        self.write_word_to_memory(addr, value);
+    }
+
+    pub fn pop_from_stack(&mut self) -> u16 {
+        let addr = self.physical_address(SegmentRegister::SS, self.sp);
+        self.sp = self.sp.wrapping_add(0x0002);
+        self.read_word_from_memory(addr)
+    }
+
+    pub fn write_byte_to_memory(&mut self, addr: u32, value: u8) {
+        let addr = addr as usize & (MEMORY_SIZE - 1);
+        self.memory[addr] = value;
+        if self.code[addr] {
+            self.code[addr] = false;
+            self.code_writes.push(addr as u32);
         }
-        let [value_low, value_high] = value.to_le_bytes();
-        self.stack.push(value_high);
-        self.stack.push(value_low);
-    }
-
-    pub fn pop_from_stack(&mut self) -> Option<u16> {
-        self.sp -= 0x0002;
-        if let Some(data_low) = self.stack.pop() {
-            if let Some(data_high) = self.stack.pop() {
-                Some(u16::from_le_bytes([data_low, data_high]))
-            } else {
-                None
-            }
-        } else {
-            None
+    }
+
+    pub fn mark_code(&mut self, addr: u32, length: usize) {
+        for i in 0..length {
+            self.code[(addr as usize + i) & (MEMORY_SIZE - 1)] = true;
         }
     }
 
-    pub fn write_byte_to_memory(&mut self, addr: u16, value: u8) {
-        self.memory.insert(addr, value);
+    // every write into marked code since the last call, marks are cleared as they are hit
+    pub fn take_code_writes(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.code_writes)
     }
 
-    pub fn write_word_to_memory(&mut self, addr: u16, value: u16) {
+    pub fn write_word_to_memory(&mut self, addr: u32, value: u16) {
         let [value_low, value_high] = value.to_le_bytes();
-        self.memory.insert(addr, value_low);
-        self.memory.insert(addr + 0b1, value_high);
+        self.write_byte_to_memory(addr, value_low);
+        self.write_byte_to_memory(addr + 0b1, value_high);
     }
 
-    pub fn read_byte_from_memory(self, addr: u16) -> Option<u8> {
-        self.memory.get(&addr).copied()
+    pub fn read_byte_from_memory(&self, addr: u32) -> u8 {
+        self.memory[addr as usize & (MEMORY_SIZE - 1)]
     }
 
-    pub fn read_word_from_memory(self, addr: u16) -> Option<u16> {
-        if let Some(value_low) = self.memory.get(&addr).copied() {
-            if let Some(value_high) = self.memory.get(&(addr + 0b1)).copied() {
-                let value = u16::from_le_bytes([value_low, value_high]);
-                Some(value)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    pub fn read_word_from_memory(&self, addr: u32) -> u16 {
+        u16::from_le_bytes([
+            self.read_byte_from_memory(addr),
+            self.read_byte_from_memory(addr + 0b1),
+        ])
     }
 
-    pub fn read_flags(self, flag: &str) -> bool {
-        match flag.to_uppercase().as_str() {
-            "CF" => ((self.flag & 0xfffe) >> 0) == 0b1,
-            "PF" => ((self.flag & 0xfffb) >> 2) == 0b1,
-            "AF" => ((self.flag & 0xffef) >> 4) == 0b1,
-            "ZF" => ((self.flag & 0xffbf) >> 6) == 0b1,
-            "SF" => ((self.flag & 0xff7f) >> 7) == 0b1,
-            "TF" => ((self.flag & 0xfeff) >> 8) == 0b1,
-            "IF" => ((self.flag & 0xfdff) >> 9) == 0b1,
-            "DF" => ((self.flag & 0xfbff) >> 10) == 0b1,
-            "OF" => ((self.flag & 0xf7ff) >> 11) == 0b1,
-            _ => panic!("Unrecognized flag"),
-        }
+    pub fn read_flag_register(&self) -> u16 {
+        self.flag
     }
 
-    pub fn write_flags(&mut self, flag: &str, status: bool) {
-        self.flag = match flag.to_uppercase().as_str() {
-            "CF" => (self.flag & 0xfffe) + ((status as u16) << 0),
-            "PF" => (self.flag & 0xfffb) + ((status as u16) << 2),
-            "AF" => (self.flag & 0xffef) + ((status as u16) << 4),
-            "ZF" => (self.flag & 0xffbf) + ((status as u16) << 6),
-            "SF" => (self.flag & 0xff7f) + ((status as u16) << 7),
-            "TF" => (self.flag & 0xfeff) + ((status as u16) << 8),
-            "IF" => (self.flag & 0xfdff) + ((status as u16) << 9),
-            "DF" => (self.flag & 0xfbff) + ((status as u16) << 10),
-            "OF" => (self.flag & 0xf7ff) + ((status as u16) << 11),
-            _ => panic!("Unrecognized flag"),
-        };
+    pub fn write_flag_register(&mut self, value: u16) {
+        self.flag = value;
+    }
+
+    pub fn read_flag(&self, flag: Flag) -> bool {
+        self.flag & flag.mask() != 0
+    }
+
+    pub fn write_flag(&mut self, flag: Flag, status: bool) {
+        if status {
+            self.flag |= flag.mask();
+        } else {
+            self.flag &= !flag.mask();
+        }
     }
 }
 
 impl Display for Hardware {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let zf = if self.clone().read_flags("ZF") {
-            "Z"
-        } else {
-            "-"
-        };
-        let sf = if self.clone().read_flags("SF") {
-            "S"
-        } else {
-            "-"
-        };
-        let of = if self.clone().read_flags("OF") {
-            "O"
-        } else {
-            "-"
-        };
-        let cf = if self.clone().read_flags("CF") {
-            "C"
-        } else {
-            "-"
-        };
+        let zf = if self.read_flag(Flag::ZF) { "Z" } else { "-" };
+        let sf = if self.read_flag(Flag::SF) { "S" } else { "-" };
+        let of = if self.read_flag(Flag::OF) { "O" } else { "-" };
+        let cf = if self.read_flag(Flag::CF) { "C" } else { "-" };
         write!(
             f,
             "{:04x} {:04x} {:04x} {:04x} {:04x} {:04x} {:04x} {:04x} {}{}{}{} {:04x}:",
@@ -242,3 +271,73 @@ impl Display for Hardware {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::register::ByteRegister::{AH, AL};
+    use crate::disassembler::register::SegmentRegister::{CS, SS};
+    use crate::disassembler::register::WordRegister::AX;
+    use crate::interpreter::hardware::{Flag, Hardware};
+
+    #[test]
+    fn test_read_write_flag() {
+        let mut hardware = Hardware::new();
+        hardware.write_flag_register(0xf002);
+        hardware.write_flag(Flag::CF, true);
+        hardware.write_flag(Flag::DF, true);
+        hardware.write_flag(Flag::OF, true);
+        assert_eq!(hardware.read_flag_register(), 0xfc03);
+        hardware.write_flag(Flag::DF, false);
+        hardware.write_flag(Flag::CF, true);
+        assert_eq!(hardware.read_flag_register(), 0xf803);
+        assert!(hardware.read_flag(Flag::OF));
+        assert!(!hardware.read_flag(Flag::ZF));
+    }
+
+    #[test]
+    fn test_read_register_by_reference() {
+        let mut hardware = Hardware::new();
+        hardware.write_to_word_register(AX, 0x1234);
+        let reference = &hardware;
+        assert_eq!(reference.read_from_word_register(AX), 0x1234);
+        assert_eq!(
+            (
+                reference.read_from_byte_register(AH),
+                reference.read_from_byte_register(AL)
+            ),
+            (0x12, 0x34)
+        );
+    }
+
+    #[test]
+    fn test_physical_address_wraps_at_1m() {
+        let mut hardware = Hardware::new();
+        (hardware.cs, hardware.ss) = (0x1234, 0xffff);
+        assert_eq!(hardware.physical_address(CS, 0x0005), 0x12345);
+        assert_eq!(hardware.physical_address(SS, 0x000f), 0xfffff);
+        assert_eq!(hardware.physical_address(SS, 0x0010), 0x00000);
+        assert_eq!(hardware.physical_address(SS, 0xffff), 0x0ffef);
+    }
+
+    #[test]
+    fn test_word_access_wraps_at_1m() {
+        let mut hardware = Hardware::new();
+        hardware.write_word_to_memory(0xfffff, 0xbeef);
+        assert_eq!(hardware.read_byte_from_memory(0xfffff), 0xef);
+        assert_eq!(hardware.read_byte_from_memory(0x00000), 0xbe);
+        assert_eq!(hardware.read_word_from_memory(0xfffff), 0xbeef);
+        // addresses past 20 bits alias the low megabyte
+        assert_eq!(hardware.read_byte_from_memory(0x100000), 0xbe);
+    }
+
+    #[test]
+    fn test_stack_wraps_at_sp_zero() {
+        let mut hardware = Hardware::new();
+        (hardware.ss, hardware.sp) = (0x2000, 0x0000);
+        hardware.push_to_stack(0x1234);
+        assert_eq!(hardware.sp, 0xfffe);
+        assert_eq!(hardware.read_word_from_memory(0x2fffe), 0x1234);
+        assert_eq!(hardware.pop_from_stack(), 0x1234);
+        assert_eq!(hardware.sp, 0x0000);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use crate::error::MmvmError;
+use crate::interpreter::hardware::{Flag, Hardware, FLAG_MASK, FLAG_RESERVED};
+use crate::interpreter::systemcall::{execute_systemcall, Minix};
+
+// the interrupt vector table sits at 0000:0000, one offset:segment pair per vector
+pub const IVT_ENTRY_SIZE: u32 = 4;
+
+pub const DIVIDE_ERROR: u8 = 0x00;
+pub const BREAKPOINT: u8 = 0x03;
+pub const OVERFLOW: u8 = 0x04;
+pub const MINIX_SYSCALL: u8 = 0x20;
+
+// A vector served on the host side, it runs in place of a guest routine and its IRET, a closure
+// may keep state between calls
+pub type Handler = Box<dyn FnMut(&mut Hardware, &mut Minix) -> Result<(), MmvmError>>;
+
+pub struct Interrupts {
+    handlers: BTreeMap<u8, Handler>,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    // the vectors a MINIX process expects its kernel to answer
+    pub fn minix() -> Self {
+        let mut interrupts = Self::new();
+        interrupts.register(MINIX_SYSCALL, execute_systemcall);
+        interrupts
+    }
+
+    pub fn register(
+        &mut self,
+        vector: u8,
+        handler: impl FnMut(&mut Hardware, &mut Minix) -> Result<(), MmvmError> + 'static,
+    ) {
+        self.handlers.insert(vector, Box::new(handler));
+    }
+
+    pub fn read_vector(vector: u8, hardware: &Hardware) -> (u16, u16) {
+        let entry = u32::from(vector) * IVT_ENTRY_SIZE;
+        (
+            hardware.read_word_from_memory(entry + 2),
+            hardware.read_word_from_memory(entry),
+        )
+    }
+
+    // an empty IVT entry means nobody installed a routine, jumping to 0000:0000 would run the table
+    pub fn is_handled(&self, vector: u8, hardware: &Hardware) -> bool {
+        self.handlers.contains_key(&vector) || Self::read_vector(vector, hardware) != (0, 0)
+    }
+
+    // Dispatch interrupt vector the way the 8086 does: push FLAGS, CS and IP, clear IF and TF,
+    // then continue at the routine in the IVT
+    pub fn raise(
+        &mut self,
+        vector: u8,
+        hardware: &mut Hardware,
+        minix: &mut Minix,
+    ) -> Result<(), MmvmError> {
+        if let Some(handler) = self.handlers.get_mut(&vector) {
+            return handler(hardware, minix);
+        }
+        if !self.is_handled(vector, hardware) {
+            return Err(MmvmError::UnhandledInterrupt {
+                address: 0x0000,
+                vector,
+            });
+        }
+        let flag = hardware.read_flag_register();
+        hardware.push_to_stack((flag & FLAG_MASK) | FLAG_RESERVED);
+        hardware.push_to_stack(hardware.cs);
+        hardware.push_to_stack(hardware.ip);
+        hardware.write_flag(Flag::IF, false);
+        hardware.write_flag(Flag::TF, false);
+        (hardware.cs, hardware.ip) = Self::read_vector(vector, hardware);
+        Ok(())
+    }
+
+    pub fn iret(hardware: &mut Hardware) {
+        hardware.ip = hardware.pop_from_stack();
+        hardware.cs = hardware.pop_from_stack();
+        let flag = hardware.pop_from_stack();
+        hardware.write_flag_register(flag & FLAG_MASK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MmvmError;
+    use crate::interpreter::hardware::Hardware;
+    use crate::interpreter::interrupt::{Interrupts, BREAKPOINT, IVT_ENTRY_SIZE, MINIX_SYSCALL};
+    use crate::interpreter::systemcall::Minix;
+
+    fn write_vector(vector: u8, segment: u16, offset: u16, hardware: &mut Hardware) {
+        let entry = u32::from(vector) * IVT_ENTRY_SIZE;
+        hardware.write_word_to_memory(entry, offset);
+        hardware.write_word_to_memory(entry + 2, segment);
+    }
+
+    fn breakpoint(hardware: &mut Hardware, _: &mut Minix) -> Result<(), MmvmError> {
+        hardware.ax = 0xcccc;
+        Ok(())
+    }
+
+    #[test]
+    fn test_raise_through_vector_table() {
+        let mut hardware = Hardware::new();
+        let mut minix = Minix::new();
+        let mut interrupts = Interrupts::new();
+        (hardware.cs, hardware.ip, hardware.sp) = (0x1000, 0x0042, 0x0100);
+        hardware.write_flag_register(0x0301);
+
+        assert_eq!(
+            interrupts.raise(BREAKPOINT, &mut hardware, &mut minix),
+            Err(MmvmError::UnhandledInterrupt {
+                address: 0x0000,
+                vector: BREAKPOINT,
+            })
+        );
+        write_vector(BREAKPOINT, 0x2000, 0x0010, &mut hardware);
+        assert_eq!(hardware.read_word_from_memory(0x000c), 0x0010);
+        interrupts
+            .raise(BREAKPOINT, &mut hardware, &mut minix)
+            .unwrap();
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x2000, 0x0010, 0x00fa)
+        );
+        assert_eq!(hardware.read_flag_register(), 0x0001);
+        assert_eq!(hardware.read_word_from_memory(0x00fa), 0x0042);
+        assert_eq!(hardware.read_word_from_memory(0x00fc), 0x1000);
+        assert_eq!(hardware.read_word_from_memory(0x00fe), 0xf303);
+
+        Interrupts::iret(&mut hardware);
+        assert_eq!(
+            (hardware.cs, hardware.ip, hardware.sp),
+            (0x1000, 0x0042, 0x0100)
+        );
+        assert_eq!(hardware.read_flag_register(), 0x0301);
+    }
+
+    #[test]
+    fn test_raise_host_handler() {
+        let mut hardware = Hardware::new();
+        let mut minix = Minix::new();
+        let mut interrupts = Interrupts::minix();
+        assert!(interrupts.is_handled(MINIX_SYSCALL, &hardware));
+        assert!(!interrupts.is_handled(BREAKPOINT, &hardware));
+
+        // a host handler takes precedence over the table and leaves the stack alone
+        write_vector(BREAKPOINT, 0x2000, 0x0010, &mut hardware);
+        interrupts.register(BREAKPOINT, breakpoint);
+        hardware.sp = 0x0100;
+        interrupts
+            .raise(BREAKPOINT, &mut hardware, &mut minix)
+            .unwrap();
+        assert_eq!(
+            (hardware.ax, hardware.sp, hardware.cs),
+            (0xcccc, 0x0100, 0x0000)
+        );
+
+        Interrupts::new()
+            .raise(BREAKPOINT, &mut hardware, &mut minix)
+            .unwrap();
+        assert_eq!((hardware.cs, hardware.ip), (0x2000, 0x0010));
+    }
+}
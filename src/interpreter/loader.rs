@@ -0,0 +1,198 @@
+use crate::disassembler::register::SegmentRegister::{CS, DS, SS};
+use crate::error::MmvmError;
+use crate::interpreter::hardware::Hardware;
+use crate::utils::header::Header;
+
+// paragraph where the process image starts, keeps 0000:0000 free for the interrupt vector table
+pub const LOAD_SEGMENT: u16 = 0x1000;
+
+fn paragraphs(bytes: u32) -> u16 {
+    ((bytes + 0xf) >> 4) as u16
+}
+
+pub fn load_image(
+    header: &Header,
+    bytes_data: &[u8],
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
+    let (a_hdrlen, a_text, a_data, a_bss) = (
+        header.a_hdrlen as usize,
+        header.a_text as usize,
+        header.a_data as usize,
+        header.a_bss as usize,
+    );
+    if bytes_data.len() < a_hdrlen + a_text + a_data {
+        return Err(MmvmError::TruncatedInput {
+            expected: a_hdrlen + a_text + a_data,
+            actual: bytes_data.len(),
+        });
+    }
+    let text_area = &bytes_data[a_hdrlen..(a_hdrlen + a_text)];
+    let data_area = &bytes_data[(a_hdrlen + a_text)..(a_hdrlen + a_text + a_data)];
+
+    // with separate I&D the data segment follows the text segment and starts at offset 0,
+    // otherwise text, data and stack share one segment and data starts right after text
+    let (data_segment, data_offset) = if header.separate_id() {
+        (LOAD_SEGMENT + paragraphs(header.a_text), 0x0000)
+    } else {
+        (LOAD_SEGMENT, a_text as u16)
+    };
+    hardware.cs = LOAD_SEGMENT;
+    hardware.ds = data_segment;
+    hardware.ss = data_segment;
+    hardware.es = data_segment;
+
+    for (offset, &byte) in text_area.iter().enumerate() {
+        let addr = hardware.physical_address(CS, offset as u16);
+        hardware.write_byte_to_memory(addr, byte);
+    }
+    for (offset, &byte) in data_area.iter().enumerate() {
+        let addr = hardware.physical_address(DS, data_offset.wrapping_add(offset as u16));
+        hardware.write_byte_to_memory(addr, byte);
+    }
+    for offset in a_data..(a_data + a_bss) {
+        let addr = hardware.physical_address(DS, data_offset.wrapping_add(offset as u16));
+        hardware.write_byte_to_memory(addr, 0x00);
+    }
+
+    // a_total is the size of the segment holding data, bss, heap and stack
+    let a_total = match header.a_total {
+        0 => 0x10000,
+        a_total => a_total.min(0x10000),
+    };
+    hardware.sp = a_total as u16;
+    hardware.brk = data_offset.wrapping_add((a_data + a_bss) as u16);
+    hardware.brk_start = hardware.brk;
+    hardware.segment_size = a_total;
+    hardware.ip = header.a_entry as u16;
+    Ok(())
+}
+
+// Build the block MINIX exec leaves on a fresh stack:
+//
+//     sp -> argc
+//           argv[0] .. argv[argc - 1], 0
+//           envp[0] .. envp[envc - 1], 0
+//           argument and environment strings
+pub fn setup_stack(args: &[String], envs: &[String], hardware: &mut Hardware) {
+    let strings_size = args
+        .iter()
+        .chain(envs.iter())
+        .map(|string| string.len() + 1)
+        .sum::<usize>();
+    let pointers_size = (1 + args.len() + 1 + envs.len() + 1) * 2;
+    let block_size = (pointers_size + strings_size + 1) & !0b1;
+
+    let base = hardware.sp.wrapping_sub(block_size as u16);
+    let mut pointer = base;
+    let mut string = base.wrapping_add(pointers_size as u16);
+
+    let mut write_word = |hardware: &mut Hardware, value: u16| {
+        let addr = hardware.physical_address(SS, pointer);
+        hardware.write_word_to_memory(addr, value);
+        pointer = pointer.wrapping_add(2);
+    };
+    write_word(hardware, args.len() as u16);
+    for strings in [args, envs] {
+        for content in strings {
+            write_word(hardware, string);
+            for &byte in content.as_bytes().iter().chain([0x00].iter()) {
+                let addr = hardware.physical_address(SS, string);
+                hardware.write_byte_to_memory(addr, byte);
+                string = string.wrapping_add(1);
+            }
+        }
+        write_word(hardware, 0x0000);
+    }
+    hardware.sp = base;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::register::SegmentRegister::{CS, DS, SS};
+    use crate::interpreter::hardware::Hardware;
+    use crate::interpreter::loader::{load_image, setup_stack, LOAD_SEGMENT};
+    use crate::utils::header::Header;
+
+    fn header(a_flags: u8) -> Header {
+        Header {
+            a_magic: [0x01, 0x03].to_vec(),
+            a_flags,
+            a_cpu: 0x04,
+            a_hdrlen: 0x20,
+            a_unused: 0x00,
+            a_version: 0x00,
+            a_text: 0x00000003,
+            a_data: 0x00000002,
+            a_bss: 0x00000004,
+            a_entry: 0x00000000,
+            a_total: 0x00010000,
+            a_syms: 0x00000000,
+            a_trsize: 0x00000000,
+            a_drsize: 0x00000000,
+            a_tbase: 0x00000000,
+            a_dbase: 0x00000000,
+        }
+    }
+
+    #[test]
+    fn test_load_separate_image() {
+        let mut bytes_data = vec![0x00; 0x20];
+        bytes_data.extend([0x90, 0x90, 0xf4, 0x34, 0x12]);
+        let mut hardware = Hardware::new();
+        load_image(&header(0x20), &bytes_data, &mut hardware).unwrap();
+
+        assert_eq!(hardware.cs, LOAD_SEGMENT);
+        assert_eq!(hardware.ds, LOAD_SEGMENT + 1);
+        assert_eq!(hardware.ss, hardware.ds);
+        assert_eq!(hardware.sp, 0x0000);
+        assert_eq!(hardware.brk, 0x0006);
+        assert_eq!(hardware.brk_start, 0x0006);
+        let text = hardware.physical_address(CS, 0x0002);
+        assert_eq!(hardware.read_byte_from_memory(text), 0xf4);
+        let data = hardware.physical_address(DS, 0x0000);
+        assert_eq!(hardware.read_word_from_memory(data), 0x1234);
+    }
+
+    #[test]
+    fn test_load_common_image() {
+        let mut bytes_data = vec![0x00; 0x20];
+        bytes_data.extend([0x90, 0x90, 0xf4, 0x34, 0x12]);
+        let mut hardware = Hardware::new();
+        load_image(&header(0x00), &bytes_data, &mut hardware).unwrap();
+
+        assert_eq!(hardware.ds, hardware.cs);
+        assert_eq!(hardware.brk, 0x0009);
+        assert_eq!(hardware.brk_start, 0x0009);
+        let data = hardware.physical_address(DS, 0x0003);
+        assert_eq!(hardware.read_word_from_memory(data), 0x1234);
+    }
+
+    #[test]
+    fn test_setup_stack() {
+        let mut hardware = Hardware::new();
+        hardware.ss = LOAD_SEGMENT;
+        let args = ["a.out".to_string(), "x".to_string()];
+        let envs = ["HOME=/".to_string()];
+        setup_stack(&args, &envs, &mut hardware);
+
+        // 6 pointers, "a.out\0x\0HOME=/\0" padded to 16 bytes
+        assert_eq!(hardware.sp, 0x10000u32.wrapping_sub(12 + 16) as u16);
+        let read_word =
+            |offset: u16| hardware.read_word_from_memory(hardware.physical_address(SS, offset));
+        let read_string = |offset: u16| {
+            (offset..)
+                .map(|offset| hardware.read_byte_from_memory(hardware.physical_address(SS, offset)))
+                .take_while(|&byte| byte != 0x00)
+                .map(char::from)
+                .collect::<String>()
+        };
+        let sp = hardware.sp;
+        assert_eq!(read_word(sp), 2);
+        assert_eq!(read_string(read_word(sp + 2)), "a.out");
+        assert_eq!(read_string(read_word(sp + 4)), "x");
+        assert_eq!(read_word(sp + 6), 0);
+        assert_eq!(read_string(read_word(sp + 8)), "HOME=/");
+        assert_eq!(read_word(sp + 10), 0);
+    }
+}
@@ -1,22 +1,45 @@
+use cache::{DecodeCache, Decoded};
 use execution::execute;
-use hardware::Hardware;
+pub use hardware::Hardware;
+use interrupt::{Interrupts, DIVIDE_ERROR};
+use loader::{load_image, setup_stack};
+use port::PortBus;
+pub use systemcall::Minix;
 
 use crate::disassembler::instruction::Instruction;
+use crate::disassembler::register::SegmentRegister::CS;
+use crate::error::MmvmError;
 use crate::utils::header::Header;
 
+mod alu;
+mod cache;
 mod execution;
 mod hardware;
+pub mod interrupt;
+mod loader;
+pub mod port;
 mod systemcall;
 mod utils;
 
 pub struct Interpreter {
     pub hardware: Hardware,
+    pub minix: Minix,
+    pub interrupts: Interrupts,
+    pub ports: PortBus,
+    pub cache: DecodeCache,
+    // print a trace line before each instruction, the benchmark turns it off
+    pub trace: bool,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self {
             hardware: Hardware::new(),
+            minix: Minix::new(),
+            interrupts: Interrupts::minix(),
+            ports: PortBus::new(),
+            cache: DecodeCache::new(),
+            trace: true,
         }
     }
 
@@ -24,28 +47,161 @@ impl Interpreter {
         &mut self,
         header: Header,
         bytes_data: &[u8],
-        instructions: Vec<(u16, Vec<u8>, Instruction)>,
-    ) {
-        let (a_hdrlen, a_text) = (header.a_hdrlen as usize, header.a_text as usize);
-        let text_area = &bytes_data[a_hdrlen..];
-        let data_area = &bytes_data[(a_hdrlen + a_text)..];
-        for (addr, &byte) in data_area.into_iter().enumerate() {
-            self.hardware.write_byte_to_memory(addr as u16, byte)
-        }
-
-        println!(" AX   BX   CX   DX   SP   BP   SI   DI  FLAG  IP");
-        for (length, binary, instruction) in instructions.into_iter() {
-            let ip = self.hardware.ip as usize;
-            let chunk = &text_area[ip..];
-            execute(chunk, &mut self.hardware);
-
-            let binary = binary
+        args: &[String],
+        envs: &[String],
+    ) -> Result<(), MmvmError> {
+        self.load(&header, bytes_data, args, envs)?;
+        if self.trace {
+            println!(" AX   BX   CX   DX   SP   BP   SI   DI  FLAG  IP");
+        }
+        while !self.hardware.halted {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn load(
+        &mut self,
+        header: &Header,
+        bytes_data: &[u8],
+        args: &[String],
+        envs: &[String],
+    ) -> Result<(), MmvmError> {
+        load_image(header, bytes_data, &mut self.hardware)?;
+        setup_stack(args, envs, &mut self.hardware);
+        Ok(())
+    }
+
+    // fetch, decode and execute the instruction at CS:IP, decoding only on a cache miss
+    pub fn step(&mut self) -> Result<(), MmvmError> {
+        let ip = self.hardware.ip;
+        let addr = self.hardware.physical_address(CS, ip);
+        let mut uncached = None;
+        if self.cache.get(addr, ip).is_none() {
+            let chunk = self.fetch();
+            let (length, instruction, word_mode) = Instruction::decode_with_size(ip, &chunk)?;
+            let decoded = Decoded {
+                ip,
+                chunk,
+                length,
+                instruction,
+                word_mode,
+            };
+            // an instruction wrapping around the end of CS isn't contiguous in memory
+            if usize::from(ip) + length <= 0x10000 {
+                self.hardware.mark_code(addr, length);
+                self.cache.insert(addr, decoded);
+            } else {
+                uncached = Some(decoded);
+            }
+        }
+        let decoded = match uncached.as_ref() {
+            Some(decoded) => decoded,
+            None => self
+                .cache
+                .get(addr, ip)
+                .expect("instruction was just cached"),
+        };
+        let bytes = &decoded.chunk[..decoded.length];
+
+        if self.trace {
+            let binary = bytes
                 .iter()
                 .map(|&b| format!("{:02x}", b))
                 .collect::<Vec<String>>()
                 .concat();
-            println!("{}{}\t\t{}", self.hardware, binary, instruction);
-            self.hardware.ip += length as u16;
+            println!("{}{}\t\t{}", self.hardware, binary, decoded.instruction);
+        }
+        self.hardware.ip = ip.wrapping_add(decoded.length as u16);
+        let result = match execute(
+            &decoded.instruction,
+            decoded.word_mode,
+            &mut self.hardware,
+            &mut self.minix,
+            &mut self.interrupts,
+            &mut self.ports,
+        ) {
+            // DIV / IDIV trap to vector 0 when the program installed a routine for it
+            Err(MmvmError::DivideError { .. })
+                if self.interrupts.is_handled(DIVIDE_ERROR, &self.hardware) =>
+            {
+                self.interrupts
+                    .raise(DIVIDE_ERROR, &mut self.hardware, &mut self.minix)
+            }
+            result => result,
+        }
+        .map_err(|error| error.at(ip, bytes));
+
+        // the program wrote over code it already ran, decode those bytes again next time
+        for addr in self.hardware.take_code_writes() {
+            self.cache.invalidate(addr);
         }
+        result
+    }
+
+    // the longest 8086 instruction is 6 bytes, plus room for a REP and a segment prefix
+    fn fetch(&self) -> [u8; 8] {
+        let mut chunk = [0u8; 8];
+        for (i, byte) in chunk.iter_mut().enumerate() {
+            let addr = self
+                .hardware
+                .physical_address(CS, self.hardware.ip.wrapping_add(i as u16));
+            *byte = self.hardware.read_byte_from_memory(addr);
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_self_modifying_code() {
+        let mut interpreter = Interpreter::new();
+        interpreter.trace = false;
+        let hardware = &mut interpreter.hardware;
+        (hardware.cs, hardware.ds, hardware.cx) = (0x1000, 0x1000, 0x0002);
+        // mov ax, 0001 / mov byte [0001], 05 / dec cx / jnz 0000 / hlt
+        let code = [
+            0xb8, 0x01, 0x00, 0xc6, 0x06, 0x01, 0x00, 0x05, 0x49, 0x75, 0xf5, 0xf4,
+        ];
+        for (offset, &byte) in code.iter().enumerate() {
+            hardware.write_byte_to_memory(0x10000 + offset as u32, byte);
+        }
+
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.hardware.ax, 0x0001);
+        while !interpreter.hardware.halted {
+            interpreter.step().unwrap();
+        }
+        // the second pass runs the patched immediate, not the cached one
+        assert_eq!(interpreter.hardware.ax, 0x0005);
+        // every pass patches the MOV again, the rest of the loop stays cached
+        assert!(interpreter.cache.get(0x10000, 0x0000).is_none());
+        assert!(interpreter.cache.get(0x10008, 0x0008).is_some());
+    }
+
+    #[test]
+    fn test_fetch_wraps_in_code_segment() {
+        let mut interpreter = Interpreter::new();
+        interpreter.trace = false;
+        let hardware = &mut interpreter.hardware;
+        (hardware.cs, hardware.ip) = (0x1000, 0xffff);
+        // mov ax, 1234 straddling the end of CS, then hlt at CS:0002
+        hardware.write_byte_to_memory(0x1ffff, 0xb8);
+        hardware.write_byte_to_memory(0x10000, 0x34);
+        hardware.write_byte_to_memory(0x10001, 0x12);
+        hardware.write_byte_to_memory(0x10002, 0xf4);
+
+        interpreter.step().unwrap();
+        assert_eq!(
+            (interpreter.hardware.ax, interpreter.hardware.ip),
+            (0x1234, 0x0002)
+        );
+        // the straddling instruction isn't contiguous in memory, so it isn't cached
+        assert!(interpreter.cache.get(0x1ffff, 0xffff).is_none());
+        interpreter.step().unwrap();
+        assert!(interpreter.hardware.halted);
     }
 }
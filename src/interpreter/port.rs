@@ -0,0 +1,132 @@
+use std::ops::RangeInclusive;
+
+// what an unmapped port reads as, nothing drives the data bus. Unmapped accesses are logged to
+// stderr so they stay out of the trace
+pub const OPEN_BUS: u8 = 0xff;
+
+// A peripheral answering IN / OUT on the ports it is attached to
+pub trait Device {
+    fn read_byte(&mut self, port: u16) -> u8;
+
+    fn write_byte(&mut self, port: u16, value: u8);
+
+    // word access goes to two consecutive ports, low byte first, unless the device knows better
+    fn read_word(&mut self, port: u16) -> u16 {
+        u16::from_le_bytes([self.read_byte(port), self.read_byte(port.wrapping_add(1))])
+    }
+
+    fn write_word(&mut self, port: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_byte(port, low);
+        self.write_byte(port.wrapping_add(1), high);
+    }
+}
+
+pub struct PortBus {
+    devices: Vec<(RangeInclusive<u16>, Box<dyn Device>)>,
+}
+
+impl PortBus {
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+        }
+    }
+
+    // the device attached last wins where ranges overlap, the mmvm binary itself attaches none
+    pub fn attach(&mut self, ports: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.devices.push((ports, device));
+    }
+
+    fn device(&mut self, port: u16) -> Option<&mut Box<dyn Device>> {
+        self.devices
+            .iter_mut()
+            .rev()
+            .find(|(ports, _)| ports.contains(&port))
+            .map(|(_, device)| device)
+    }
+
+    pub fn read_byte(&mut self, port: u16) -> u8 {
+        match self.device(port) {
+            Some(device) => device.read_byte(port),
+            None => {
+                eprintln!("<in(0x{:04x}) => 0x{:02x}, unmapped>", port, OPEN_BUS);
+                OPEN_BUS
+            }
+        }
+    }
+
+    pub fn write_byte(&mut self, port: u16, value: u8) {
+        match self.device(port) {
+            Some(device) => device.write_byte(port, value),
+            None => eprintln!("<out(0x{:04x}, 0x{:02x}) unmapped>", port, value),
+        }
+    }
+
+    pub fn read_word(&mut self, port: u16) -> u16 {
+        match self.device(port) {
+            Some(device) => device.read_word(port),
+            None => {
+                eprintln!("<in(0x{:04x}) => 0xffff, unmapped>", port);
+                u16::from_le_bytes([OPEN_BUS, OPEN_BUS])
+            }
+        }
+    }
+
+    pub fn write_word(&mut self, port: u16, value: u16) {
+        match self.device(port) {
+            Some(device) => device.write_word(port, value),
+            None => eprintln!("<out(0x{:04x}, 0x{:04x}) unmapped>", port, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::interpreter::port::{Device, PortBus};
+
+    // a transmit register at the base port and a line status register that is always ready
+    struct Serial {
+        output: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Device for Serial {
+        fn read_byte(&mut self, port: u16) -> u8 {
+            match port {
+                0x03fd => 0x20,
+                _ => 0x00,
+            }
+        }
+
+        fn write_byte(&mut self, port: u16, value: u8) {
+            if port == 0x03f8 {
+                self.output.borrow_mut().push(value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_port_bus() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = PortBus::new();
+        bus.attach(
+            0x03f8..=0x03ff,
+            Box::new(Serial {
+                output: output.clone(),
+            }),
+        );
+        bus.write_byte(0x03f8, b'h');
+        bus.write_word(0x03f8, u16::from(b'i'));
+        assert_eq!(*output.borrow(), b"hi".to_vec());
+        assert_eq!(bus.read_byte(0x03fd), 0x20);
+        assert_eq!(bus.read_word(0x03fc), 0x2000);
+
+        assert_eq!(bus.read_byte(0x0060), 0xff);
+        assert_eq!(bus.read_word(0x0060), 0xffff);
+        bus.write_byte(0x0060, 0x00);
+        assert_eq!(output.borrow().len(), 2);
+    }
+}
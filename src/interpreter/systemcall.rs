@@ -1,30 +1,776 @@
-use std::process::exit;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 
+use crate::disassembler::register::SegmentRegister::DS;
+use crate::error::MmvmError;
 use crate::interpreter::hardware::Hardware;
 
-pub fn execute_systemcall(hardware: &mut Hardware) {
-    match hardware.systemcall_count {
-        0 => write_systemcall(1, hardware),
-        1 => exit_systemcall(),
-        _ => todo!(),
+// MINIX message is m_source, m_type and a 20-byte union of the m1 .. m9 layouts
+pub const MESSAGE_SIZE: usize = 24;
+pub const M_TYPE: usize = 2;
+pub const M1_I1: usize = 4;
+pub const M1_I2: usize = 6;
+pub const M1_I3: usize = 8;
+pub const M1_P1: usize = 10;
+pub const M2_I1: usize = 4;
+pub const M2_I2: usize = 6;
+pub const M2_I3: usize = 8;
+pub const M2_L1: usize = 10;
+pub const M2_P1: usize = 18;
+pub const M3_I2: usize = 6;
+pub const M3_P1: usize = 8;
+
+// system call numbers from <minix/callnr.h>
+pub const EXIT: u16 = 1;
+pub const READ: u16 = 3;
+pub const WRITE: u16 = 4;
+pub const OPEN: u16 = 5;
+pub const CLOSE: u16 = 6;
+pub const CREAT: u16 = 8;
+pub const UNLINK: u16 = 10;
+pub const BRK: u16 = 17;
+pub const LSEEK: u16 = 19;
+pub const IOCTL: u16 = 54;
+// number of entries in the MINIX system call table, anything past it is not a system call at all
+pub const NCALLS: u16 = 78;
+
+// error numbers from <errno.h>, replied negated in m_type
+pub const ENOENT: i16 = 2;
+pub const EIO: i16 = 5;
+pub const EBADF: i16 = 9;
+pub const ENOMEM: i16 = 12;
+pub const EACCES: i16 = 13;
+pub const EEXIST: i16 = 17;
+pub const EINVAL: i16 = 22;
+pub const EMFILE: i16 = 24;
+
+// open flags from <fcntl.h>
+pub const O_ACCMODE: u16 = 0o3;
+pub const O_WRONLY: u16 = 0o1;
+pub const O_RDWR: u16 = 0o2;
+pub const O_CREAT: u16 = 0o100;
+pub const O_EXCL: u16 = 0o200;
+pub const O_TRUNC: u16 = 0o1000;
+pub const O_APPEND: u16 = 0o2000;
+
+// OPEN_MAX from <limits.h>
+pub const OPEN_MAX: usize = 20;
+
+#[derive(Debug)]
+pub enum Descriptor {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+// Process state kept on the host side of the system call interface
+#[derive(Debug)]
+pub struct Minix {
+    pub root: Option<PathBuf>,
+    files: Vec<Option<Descriptor>>,
+}
+
+impl Minix {
+    pub fn new() -> Self {
+        let mut files: Vec<Option<Descriptor>> = (0..OPEN_MAX).map(|_| None).collect();
+        files[0] = Some(Descriptor::Stdin);
+        files[1] = Some(Descriptor::Stdout);
+        files[2] = Some(Descriptor::Stderr);
+        Self { root: None, files }
+    }
+
+    // With a root every path, absolute or relative, is resolved inside it and `..` never climbs out
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        match &self.root {
+            None => PathBuf::from(path),
+            Some(root) => {
+                let mut resolved = PathBuf::new();
+                for component in Path::new(path).components() {
+                    match component {
+                        Component::Normal(name) => resolved.push(name),
+                        Component::ParentDir => {
+                            resolved.pop();
+                        }
+                        Component::RootDir | Component::Prefix(_) => resolved.clear(),
+                        Component::CurDir => {}
+                    }
+                }
+                root.join(resolved)
+            }
+        }
+    }
+
+    // The lexical resolution can't see symlinks, so the host path is followed and has to stay in
+    // the root. A file that doesn't exist yet is checked through its parent
+    pub fn host_path(&self, path: &str) -> Result<PathBuf, i16> {
+        let resolved = self.resolve_path(path);
+        let Some(root) = &self.root else {
+            return Ok(resolved);
+        };
+        let root = root.canonicalize().map_err(|error| errno(&error))?;
+        let real = match (resolved.symlink_metadata(), resolved.parent()) {
+            (Err(_), Some(parent)) => parent.canonicalize(),
+            _ => resolved.canonicalize(),
+        }
+        .map_err(|error| errno(&error))?;
+        if real.starts_with(&root) {
+            Ok(resolved)
+        } else {
+            Err(-EACCES)
+        }
+    }
+
+    fn allocate(&mut self, descriptor: Descriptor) -> i16 {
+        match self.files.iter().position(|file| file.is_none()) {
+            Some(fd) => {
+                self.files[fd] = Some(descriptor);
+                fd as i16
+            }
+            None => -EMFILE,
+        }
+    }
+
+    fn descriptor(&mut self, fd: u16) -> Option<&mut Descriptor> {
+        self.files
+            .get_mut(fd as usize)
+            .and_then(|file| file.as_mut())
+    }
+}
+
+fn errno(error: &io::Error) -> i16 {
+    match error.kind() {
+        io::ErrorKind::NotFound => -ENOENT,
+        io::ErrorKind::PermissionDenied => -EACCES,
+        io::ErrorKind::AlreadyExists => -EEXIST,
+        io::ErrorKind::InvalidInput => -EINVAL,
+        _ => -EIO,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Message {
+    bytes: [u8; MESSAGE_SIZE],
+}
+
+impl Message {
+    pub fn read(hardware: &Hardware, offset: u16) -> Self {
+        let mut bytes = [0x00; MESSAGE_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let addr = hardware.physical_address(DS, offset.wrapping_add(i as u16));
+            *byte = hardware.read_byte_from_memory(addr);
+        }
+        Self { bytes }
+    }
+
+    pub fn write(&self, hardware: &mut Hardware, offset: u16) {
+        for (i, &byte) in self.bytes.iter().enumerate() {
+            let addr = hardware.physical_address(DS, offset.wrapping_add(i as u16));
+            hardware.write_byte_to_memory(addr, byte);
+        }
+    }
+
+    pub fn word(&self, field: usize) -> u16 {
+        u16::from_le_bytes([self.bytes[field], self.bytes[field + 1]])
     }
+
+    pub fn set_word(&mut self, field: usize, value: u16) {
+        self.bytes[field..(field + 2)].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn long(&self, field: usize) -> u32 {
+        u32::from_le_bytes(self.bytes[field..(field + 4)].try_into().unwrap())
+    }
+
+    pub fn set_long(&mut self, field: usize, value: u32) {
+        self.bytes[field..(field + 4)].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn m_type(&self) -> u16 {
+        self.word(M_TYPE)
+    }
+}
+
+fn read_bytes(hardware: &Hardware, offset: u16, length: u16) -> Vec<u8> {
+    (0..length)
+        .map(|i| {
+            hardware.read_byte_from_memory(hardware.physical_address(DS, offset.wrapping_add(i)))
+        })
+        .collect()
+}
+
+fn write_bytes(hardware: &mut Hardware, offset: u16, content: &[u8]) {
+    for (i, &byte) in content.iter().enumerate() {
+        let addr = hardware.physical_address(DS, offset.wrapping_add(i as u16));
+        hardware.write_byte_to_memory(addr, byte);
+    }
+}
+
+fn read_string(hardware: &Hardware, offset: u16) -> String {
+    let content = (0..u16::MAX)
+        .map(|i| {
+            hardware.read_byte_from_memory(hardware.physical_address(DS, offset.wrapping_add(i)))
+        })
+        .take_while(|&byte| byte != 0x00)
+        .collect::<Vec<u8>>();
+    String::from_utf8_lossy(&content).into_owned()
 }
 
-pub fn write_systemcall(fd: i32, hardware: &mut Hardware) {
+// INT 20h: BX points to the request message, the reply overwrites it and AX holds the sendrec status
+pub fn execute_systemcall(hardware: &mut Hardware, minix: &mut Minix) -> Result<(), MmvmError> {
     let message_address = hardware.bx;
-    let content = "#message";
+    let mut message = Message::read(hardware, message_address);
+    let result = match message.m_type() {
+        EXIT => exit_systemcall(&message, hardware),
+        READ => read_systemcall(&message, hardware, minix),
+        WRITE => write_systemcall(&message, hardware, minix),
+        OPEN => open_systemcall(&message, hardware, minix),
+        CLOSE => close_systemcall(&message, minix),
+        CREAT => creat_systemcall(&message, hardware, minix),
+        UNLINK => unlink_systemcall(&message, hardware, minix),
+        BRK => brk_systemcall(&mut message, hardware),
+        LSEEK => lseek_systemcall(&mut message, minix),
+        IOCTL => ioctl_systemcall(&message),
+        call if call == 0 || call >= NCALLS => {
+            return Err(MmvmError::InvalidSyscall {
+                address: 0x0000,
+                call,
+            });
+        }
+        call => {
+            println!("<unsupported system call {}>", call);
+            -EINVAL
+        }
+    };
+    message.set_word(M_TYPE, result as u16);
+    message.write(hardware, message_address);
+    hardware.ax = 0x0000;
+    Ok(())
+}
+
+pub fn read_systemcall(message: &Message, hardware: &mut Hardware, minix: &mut Minix) -> i16 {
+    let (fd, nbytes, buffer) = (
+        message.word(M1_I1),
+        message.word(M1_I2),
+        message.word(M1_P1),
+    );
+    // the count comes back in a signed word, a longer transfer would read as an error
+    let mut content = vec![0x00; nbytes.min(i16::MAX as u16) as usize];
+    let result = match minix.descriptor(fd) {
+        Some(Descriptor::Stdin) => Some(io::stdin().read(&mut content)),
+        Some(Descriptor::File(file)) => Some(file.read(&mut content)),
+        Some(Descriptor::Stdout) | Some(Descriptor::Stderr) | None => None,
+    };
+    let result = match result {
+        Some(Ok(length)) => {
+            write_bytes(hardware, buffer, &content[..length]);
+            length as i16
+        }
+        Some(Err(error)) => errno(&error),
+        None => -EBADF,
+    };
+    println!("<read({}, 0x{:04x}, {}) => {}>", fd, buffer, nbytes, result);
+    result
+}
+
+pub fn write_systemcall(message: &Message, hardware: &mut Hardware, minix: &mut Minix) -> i16 {
+    let (fd, nbytes, buffer) = (
+        message.word(M1_I1),
+        message.word(M1_I2),
+        message.word(M1_P1),
+    );
+    // the count comes back in a signed word, a longer transfer is cut short like a partial write
+    let length = nbytes.min(i16::MAX as u16);
+    let content = read_bytes(hardware, buffer, length);
+    // stdout and stderr are echoed into the trace, which is the host's stdout
+    let (result, echo) = match minix.descriptor(fd) {
+        Some(Descriptor::Stdout) | Some(Descriptor::Stderr) => (
+            length as i16,
+            String::from_utf8_lossy(&content).into_owned(),
+        ),
+        Some(Descriptor::File(file)) => match file.write_all(&content) {
+            Ok(()) => (length as i16, String::new()),
+            Err(error) => (errno(&error), String::new()),
+        },
+        Some(Descriptor::Stdin) | None => (-EBADF, String::new()),
+    };
     println!(
-        "<write({}, {:04x}, {}){}=> {}>",
+        "<write({}, 0x{:04x}, {}){} => {}>",
+        fd, buffer, nbytes, echo, result
+    );
+    result
+}
+
+fn open_file(minix: &mut Minix, name: &str, flags: u16) -> i16 {
+    let mut options = OpenOptions::new();
+    match flags & O_ACCMODE {
+        O_WRONLY => options.write(true),
+        O_RDWR => options.read(true).write(true),
+        _ => options.read(true),
+    };
+    options
+        .append((flags & O_APPEND) != 0)
+        .truncate((flags & O_TRUNC) != 0);
+    if (flags & O_CREAT) != 0 {
+        if (flags & O_EXCL) != 0 {
+            options.create_new(true);
+        } else {
+            options.create(true);
+        }
+    }
+    let path = match minix.host_path(name) {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+    match options.open(path) {
+        Ok(file) => minix.allocate(Descriptor::File(file)),
+        Err(error) => errno(&error),
+    }
+}
+
+pub fn open_systemcall(message: &Message, hardware: &Hardware, minix: &mut Minix) -> i16 {
+    // libc sends m1 (with mode) when O_CREAT is set and m3 otherwise, flags sit at the same offset
+    let flags = message.word(M1_I2);
+    let name = if (flags & O_CREAT) != 0 {
+        read_string(hardware, message.word(M1_P1))
+    } else {
+        read_string(hardware, message.word(M3_P1))
+    };
+    let result = open_file(minix, &name, flags);
+    if (flags & O_CREAT) != 0 {
+        println!(
+            "<open(\"{}\", 0{:o}, 0{:o}) => {}>",
+            name,
+            flags,
+            message.word(M1_I3),
+            result
+        );
+    } else {
+        println!("<open(\"{}\", 0{:o}) => {}>", name, flags, result);
+    }
+    result
+}
+
+pub fn creat_systemcall(message: &Message, hardware: &Hardware, minix: &mut Minix) -> i16 {
+    let (mode, name) = (
+        message.word(M3_I2),
+        read_string(hardware, message.word(M3_P1)),
+    );
+    let result = open_file(minix, &name, O_WRONLY | O_CREAT | O_TRUNC);
+    println!("<creat(\"{}\", 0{:o}) => {}>", name, mode, result);
+    result
+}
+
+pub fn close_systemcall(message: &Message, minix: &mut Minix) -> i16 {
+    let fd = message.word(M1_I1);
+    let result = match minix.files.get_mut(fd as usize) {
+        Some(file @ Some(_)) => {
+            *file = None;
+            0
+        }
+        _ => -EBADF,
+    };
+    println!("<close({}) => {}>", fd, result);
+    result
+}
+
+pub fn unlink_systemcall(message: &Message, hardware: &Hardware, minix: &Minix) -> i16 {
+    let name = read_string(hardware, message.word(M3_P1));
+    let result = match minix.host_path(&name).map(fs::remove_file) {
+        Ok(Ok(())) => 0,
+        Ok(Err(error)) => errno(&error),
+        Err(code) => code,
+    };
+    println!("<unlink(\"{}\") => {}>", name, result);
+    result
+}
+
+// the heap grows from the end of bss towards the stack, it may not drop into bss or pass SP or
+// the end of a_total
+pub fn brk_systemcall(message: &mut Message, hardware: &mut Hardware) -> i16 {
+    let addr = message.word(M1_P1);
+    let result = if addr < hardware.brk_start
+        || addr >= hardware.sp
+        || u32::from(addr) > hardware.segment_size
+    {
+        -ENOMEM
+    } else {
+        for offset in hardware.brk..addr {
+            let addr = hardware.physical_address(DS, offset);
+            hardware.write_byte_to_memory(addr, 0x00);
+        }
+        hardware.brk = addr;
+        0
+    };
+    message.set_word(M2_P1, hardware.brk);
+    println!("<brk(0x{:04x}) => {}>", addr, result);
+    result
+}
+
+pub fn lseek_systemcall(message: &mut Message, minix: &mut Minix) -> i16 {
+    let (fd, offset, whence) = (
+        message.word(M2_I1),
+        message.long(M2_L1) as i32,
+        message.word(M2_I2),
+    );
+    let position = match whence {
+        0 if offset >= 0 => Some(SeekFrom::Start(offset as u64)),
+        1 => Some(SeekFrom::Current(i64::from(offset))),
+        2 => Some(SeekFrom::End(i64::from(offset))),
+        _ => None,
+    };
+    let result = match (minix.descriptor(fd), position) {
+        (Some(Descriptor::File(file)), Some(position)) => match file.seek(position) {
+            Ok(position) => {
+                message.set_long(M2_L1, position as u32);
+                0
+            }
+            Err(error) => errno(&error),
+        },
+        (Some(Descriptor::File(_)), None) => -EINVAL,
+        _ => -EBADF,
+    };
+    println!(
+        "<lseek({}, {}, {}) => {}>",
         fd,
-        message_address,
-        content.len(),
-        content,
-        content.len()
+        offset,
+        whence,
+        if result == 0 {
+            i64::from(message.long(M2_L1))
+        } else {
+            i64::from(result)
+        }
     );
+    result
 }
 
-pub fn exit_systemcall() {
-    let return_code = 0;
+pub fn ioctl_systemcall(message: &Message) -> i16 {
+    let (fd, request, argument) = (
+        message.word(M2_I1),
+        message.word(M2_I3),
+        message.word(M2_P1),
+    );
+    // no terminal is emulated, so stdio treats every descriptor as a plain file
+    println!("<ioctl({}, 0x{:04x}, 0x{:04x})>", fd, request, argument);
+    -EINVAL
+}
+
+pub fn exit_systemcall(message: &Message, hardware: &mut Hardware) -> i16 {
+    let return_code = message.word(M1_I1) as i16;
     println!("<exit({})>", return_code);
-    exit(0)
+    hardware.halted = true;
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::register::SegmentRegister::DS;
+    use crate::interpreter::hardware::Hardware;
+    use crate::interpreter::systemcall::*;
+
+    fn send(hardware: &mut Hardware, message: &Message) -> Message {
+        send_to(hardware, &mut Minix::new(), message)
+    }
+
+    fn send_to(hardware: &mut Hardware, minix: &mut Minix, message: &Message) -> Message {
+        hardware.bx = 0x0100;
+        message.write(hardware, 0x0100);
+        execute_systemcall(hardware, minix).unwrap();
+        Message::read(hardware, 0x0100)
+    }
+
+    fn request(m_type: u16, fields: &[(usize, u16)]) -> Message {
+        let mut message = Message { bytes: [0x00; 24] };
+        message.set_word(M_TYPE, m_type);
+        for &(field, value) in fields {
+            message.set_word(field, value);
+        }
+        message
+    }
+
+    fn store(hardware: &mut Hardware, offset: u16, content: &[u8]) {
+        for (i, &byte) in content.iter().enumerate() {
+            let addr = hardware.physical_address(DS, offset + i as u16);
+            hardware.write_byte_to_memory(addr, byte);
+        }
+    }
+
+    #[test]
+    fn test_write_systemcall() {
+        let mut hardware = Hardware::new();
+        hardware.ax = 0x0001;
+        for (offset, &byte) in b"hello\n".iter().enumerate() {
+            let addr = hardware.physical_address(DS, 0x0200 + offset as u16);
+            hardware.write_byte_to_memory(addr, byte);
+        }
+        let mut message = Message::read(&hardware, 0x0100);
+        message.set_word(M_TYPE, WRITE);
+        message.set_word(M1_I1, 1);
+        message.set_word(M1_I2, 6);
+        message.set_word(M1_P1, 0x0200);
+
+        let reply = send(&mut hardware, &message);
+        assert_eq!(reply.m_type(), 6);
+        assert_eq!(hardware.ax, 0x0000);
+        assert!(!hardware.halted);
+    }
+
+    #[test]
+    fn test_exit_systemcall() {
+        let mut hardware = Hardware::new();
+        let mut message = Message::read(&hardware, 0x0100);
+        message.set_word(M_TYPE, EXIT);
+        let reply = send(&mut hardware, &message);
+        assert_eq!(reply.m_type(), 0);
+        assert!(hardware.halted);
+    }
+
+    #[test]
+    fn test_unsupported_systemcall() {
+        let mut hardware = Hardware::new();
+        let mut message = Message::read(&hardware, 0x0100);
+        message.set_word(M_TYPE, 0x002f);
+        let reply = send(&mut hardware, &message);
+        assert_eq!(reply.m_type() as i16, -EINVAL);
+    }
+
+    #[test]
+    fn test_invalid_systemcall() {
+        let mut hardware = Hardware::new();
+        hardware.bx = 0x0100;
+        request(0x00ff, &[]).write(&mut hardware, 0x0100);
+        assert_eq!(
+            execute_systemcall(&mut hardware, &mut Minix::new()),
+            Err(MmvmError::InvalidSyscall {
+                address: 0x0000,
+                call: 0x00ff
+            })
+        );
+    }
+
+    #[test]
+    fn test_brk_systemcall() {
+        let mut hardware = Hardware::new();
+        hardware.sp = 0x8000;
+        hardware.brk = 0x1000;
+        hardware.brk_start = 0x1000;
+        store(&mut hardware, 0x1000, &[0xff; 0x10]);
+
+        let reply = send(&mut hardware, &request(BRK, &[(M1_P1, 0x1010)]));
+        assert_eq!(reply.m_type(), 0);
+        assert_eq!(reply.word(M2_P1), 0x1010);
+        assert_eq!(hardware.brk, 0x1010);
+        assert_eq!(read_bytes(&hardware, 0x1000, 0x10), vec![0x00; 0x10]);
+
+        let reply = send(&mut hardware, &request(BRK, &[(M1_P1, 0x8000)]));
+        assert_eq!(reply.m_type() as i16, -ENOMEM);
+        assert_eq!(reply.word(M2_P1), 0x1010);
+        assert_eq!(hardware.brk, 0x1010);
+
+        // below the end of bss would let a later grow zero live data
+        let reply = send(&mut hardware, &request(BRK, &[(M1_P1, 0x0fff)]));
+        assert_eq!(reply.m_type() as i16, -ENOMEM);
+        assert_eq!(hardware.brk, 0x1010);
+        let reply = send(&mut hardware, &request(BRK, &[(M1_P1, 0x1000)]));
+        assert_eq!(reply.m_type(), 0);
+        assert_eq!(hardware.brk, 0x1000);
+
+        hardware.sp = 0xff00;
+        hardware.segment_size = 0x9000;
+        let reply = send(&mut hardware, &request(BRK, &[(M1_P1, 0x9001)]));
+        assert_eq!(reply.m_type() as i16, -ENOMEM);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let mut minix = Minix::new();
+        assert_eq!(minix.resolve_path("a/b"), PathBuf::from("a/b"));
+        minix.root = Some(PathBuf::from("/sandbox"));
+        let testcases = [
+            ("/etc/passwd", "/sandbox/etc/passwd"),
+            ("input.txt", "/sandbox/input.txt"),
+            ("../../etc", "/sandbox/etc"),
+            ("./a/../b", "/sandbox/b"),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            assert_eq!(
+                minix.resolve_path(testcase.0),
+                PathBuf::from(testcase.1),
+                "#{}, expected: {}",
+                i,
+                testcase.1
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_systemcall() {
+        let root = std::env::temp_dir().join(format!("mmvm-systemcall-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut minix = Minix::new();
+        minix.root = Some(root.clone());
+        let mut hardware = Hardware::new();
+        store(&mut hardware, 0x0200, b"/report.txt\0");
+        store(&mut hardware, 0x0300, b"0123456789");
+
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(CREAT, &[(M3_I2, 0o644), (M3_P1, 0x0200)]),
+        );
+        let fd = reply.m_type();
+        assert_eq!(fd, 3);
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(WRITE, &[(M1_I1, fd), (M1_I2, 10), (M1_P1, 0x0300)]),
+        );
+        assert_eq!(reply.m_type(), 10);
+        assert_eq!(
+            std::fs::read(root.join("report.txt")).unwrap(),
+            b"0123456789"
+        );
+
+        let mut message = request(LSEEK, &[(M2_I1, fd), (M2_I2, 0)]);
+        message.set_long(M2_L1, 4);
+        let reply = send_to(&mut hardware, &mut minix, &message);
+        assert_eq!(reply.m_type(), 0);
+        assert_eq!(reply.long(M2_L1), 4);
+        let reply = send_to(&mut hardware, &mut minix, &request(CLOSE, &[(M1_I1, fd)]));
+        assert_eq!(reply.m_type(), 0);
+
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(OPEN, &[(M3_I2, 0), (M3_P1, 0x0200)]),
+        );
+        let fd = reply.m_type();
+        assert_eq!(fd, 3);
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(READ, &[(M1_I1, fd), (M1_I2, 64), (M1_P1, 0x0400)]),
+        );
+        assert_eq!(reply.m_type(), 10);
+        assert_eq!(read_string(&hardware, 0x0400), "0123456789");
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(READ, &[(M1_I1, 7), (M1_I2, 64), (M1_P1, 0x0400)]),
+        );
+        assert_eq!(reply.m_type() as i16, -EBADF);
+
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(UNLINK, &[(M3_P1, 0x0200)]),
+        );
+        assert_eq!(reply.m_type(), 0);
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(OPEN, &[(M3_I2, 0), (M3_P1, 0x0200)]),
+        );
+        assert_eq!(reply.m_type() as i16, -ENOENT);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_out_of_root() {
+        let temp = std::env::temp_dir().join(format!("mmvm-symlink-{}", std::process::id()));
+        let (root, outside) = (temp.join("root"), temp.join("outside"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret"), b"secret").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("new"), root.join("dangling")).unwrap();
+        std::fs::create_dir_all(root.join("inside")).unwrap();
+        std::os::unix::fs::symlink(root.join("inside"), root.join("alias")).unwrap();
+        let mut minix = Minix::new();
+        minix.root = Some(root.clone());
+
+        let testcases = [
+            ("/link/secret", Err(-EACCES)),
+            ("/link/new", Err(-EACCES)),
+            ("/link", Err(-EACCES)),
+            ("/dangling", Err(-ENOENT)),
+            ("/alias/file", Ok(root.join("alias/file"))),
+            ("/missing/file", Err(-ENOENT)),
+        ];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            assert_eq!(minix.host_path(testcase.0), testcase.1, "#{}", i);
+        }
+
+        let mut hardware = Hardware::new();
+        store(&mut hardware, 0x0200, b"/link/secret\0");
+        store(&mut hardware, 0x0300, b"/link/new\0");
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(OPEN, &[(M3_I2, 0), (M3_P1, 0x0200)]),
+        );
+        assert_eq!(reply.m_type() as i16, -EACCES);
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(CREAT, &[(M3_I2, 0o644), (M3_P1, 0x0300)]),
+        );
+        assert_eq!(reply.m_type() as i16, -EACCES);
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(UNLINK, &[(M3_P1, 0x0200)]),
+        );
+        assert_eq!(reply.m_type() as i16, -EACCES);
+        assert!(outside.join("secret").exists());
+        assert!(!outside.join("new").exists());
+        std::fs::remove_dir_all(&temp).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_larger_than_signed_word() {
+        let root = std::env::temp_dir().join(format!("mmvm-transfer-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut minix = Minix::new();
+        minix.root = Some(root.clone());
+        let mut hardware = Hardware::new();
+        store(&mut hardware, 0x0000, b"/big\0");
+
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(CREAT, &[(M3_I2, 0o644), (M3_P1, 0x0000)]),
+        );
+        let fd = reply.m_type();
+        let testcases = [(0x7fff, 0x7fff), (0x8000, 0x7fff), (0xffff, 0x7fff)];
+        for (i, testcase) in testcases.into_iter().enumerate() {
+            let reply = send_to(
+                &mut hardware,
+                &mut minix,
+                &request(WRITE, &[(M1_I1, fd), (M1_I2, testcase.0), (M1_P1, 0x0000)]),
+            );
+            assert_eq!(reply.m_type(), testcase.1, "#{}", i);
+        }
+        assert_eq!(
+            std::fs::metadata(root.join("big")).unwrap().len(),
+            3 * 0x7fff
+        );
+        send_to(&mut hardware, &mut minix, &request(CLOSE, &[(M1_I1, fd)]));
+
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(OPEN, &[(M3_I2, 0), (M3_P1, 0x0000)]),
+        );
+        let fd = reply.m_type();
+        let reply = send_to(
+            &mut hardware,
+            &mut minix,
+            &request(READ, &[(M1_I1, fd), (M1_I2, 0x9000), (M1_P1, 0x0000)]),
+        );
+        assert_eq!(reply.m_type(), 0x7fff);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -1,70 +1,103 @@
 use crate::disassembler::addressing::Addressing;
 use crate::disassembler::direction::Direction;
 
-use crate::disassembler::numerical::{Immediate, Numerical};
-use crate::disassembler::register::SegmentRegister::DS;
+use crate::disassembler::numerical::{Displacement, Immediate, Numerical};
+use crate::disassembler::register::SegmentRegister::{DS, SS};
 use crate::disassembler::register::WordRegister::{BP, BX, DI, SI};
 use crate::disassembler::register::{BaseRegister, IndexRegister, Register};
+use crate::error::MmvmError;
 use crate::interpreter::hardware::Hardware;
 
-pub fn calculate_effective_address(r_m: &Addressing, hardware: &Hardware) -> Option<u16> {
-    match r_m {
-        &Addressing::RegisterAddressing(_) => None,
-        &Addressing::DirectAddressing(Numerical::Imme(Immediate::UnsignedWord(addr))) => Some(addr),
-        &Addressing::DirectIndexAddressing(
-            Numerical::Imme(Immediate::UnsignedWord(offset)),
-            Numerical::Imme(Immediate::UnsignedWord(segment)),
-        ) => Some(segment << 4 + offset),
-        &Addressing::BasedAddressing(base, Numerical::Disp(disp)) => {
-            let address = ((hardware.clone().read_from_segment_register(DS) << 4) as i16
-                + match base {
-                    BaseRegister::BX => hardware.clone().read_from_word_register(BX) as i16,
-                    BaseRegister::BP => hardware.clone().read_from_word_register(BP) as i16,
-                }
-                + match disp {
-                    crate::disassembler::numerical::Displacement::SignedWord(disp) => disp,
-                    crate::disassembler::numerical::Displacement::SignedByte(disp) => {
-                        i16::from(disp)
-                    }
-                    _ => return None,
-                }) as u16;
-            Some(address)
+// the faulting address and bytes are filled in by the interpreter, see MmvmError::at
+pub fn undecodable() -> MmvmError {
+    MmvmError::UndecodableOpcode {
+        address: 0x0000,
+        bytes: Vec::new(),
+    }
+}
+
+pub fn unimplemented() -> MmvmError {
+    MmvmError::UnimplementedInstruction {
+        address: 0x0000,
+        bytes: Vec::new(),
+    }
+}
+
+pub fn divide_error() -> MmvmError {
+    MmvmError::DivideError {
+        address: 0x0000,
+        bytes: Vec::new(),
+    }
+}
+
+pub fn memory_fault() -> MmvmError {
+    MmvmError::MemoryFault {
+        address: 0x0000,
+        bytes: Vec::new(),
+    }
+}
+
+// displacements and immediates as a word, signed bytes are sign-extended
+pub fn numerical_value(disp: &Numerical) -> u16 {
+    match disp {
+        &Numerical::Disp(Displacement::UnsignedWord(disp))
+        | &Numerical::Imme(Immediate::UnsignedWord(disp)) => disp,
+        &Numerical::Disp(Displacement::SignedWord(disp))
+        | &Numerical::Imme(Immediate::SignedWord(disp)) => disp as u16,
+        &Numerical::Disp(Displacement::UnsignedByte(disp))
+        | &Numerical::Imme(Immediate::UnsignedByte(disp)) => u16::from(disp),
+        &Numerical::Disp(Displacement::SignedByte(disp))
+        | &Numerical::Imme(Immediate::SignedByte(disp)) => i16::from(disp) as u16,
+    }
+}
+
+fn base_value(base: BaseRegister, hardware: &Hardware) -> u16 {
+    match base {
+        BaseRegister::BX => hardware.read_from_word_register(BX),
+        BaseRegister::BP => hardware.read_from_word_register(BP),
+    }
+}
+
+fn index_value(index: IndexRegister, hardware: &Hardware) -> u16 {
+    match index {
+        IndexRegister::DI => hardware.read_from_word_register(DI),
+        IndexRegister::SI => hardware.read_from_word_register(SI),
+    }
+}
+
+pub fn calculate_offset(r_m: &Addressing, hardware: &Hardware) -> Option<u16> {
+    let offset = match r_m {
+        &Addressing::RegisterAddressing(_) => return None,
+        &Addressing::DirectAddressing(addr) => numerical_value(&addr),
+        &Addressing::DirectIndexAddressing(_, _) => return None,
+        &Addressing::BasedAddressing(base, disp) => {
+            base_value(base, hardware).wrapping_add(numerical_value(&disp))
         }
-        &Addressing::IndexedAddressing(index, Numerical::Disp(disp)) => {
-            let address = ((hardware.clone().read_from_segment_register(DS) << 4) as i16
-                + match index {
-                    IndexRegister::DI => hardware.clone().read_from_word_register(DI) as i16,
-                    IndexRegister::SI => hardware.clone().read_from_word_register(SI) as i16,
-                }
-                + match disp {
-                    crate::disassembler::numerical::Displacement::SignedWord(disp) => disp,
-                    crate::disassembler::numerical::Displacement::SignedByte(disp) => {
-                        i16::from(disp)
-                    }
-                    _ => return None,
-                }) as u16;
-            Some(address)
+        &Addressing::IndexedAddressing(index, disp) => {
+            index_value(index, hardware).wrapping_add(numerical_value(&disp))
         }
-        &Addressing::BasedIndexedAddressing(base, index, Numerical::Disp(disp)) => {
-            let address = ((hardware.clone().read_from_segment_register(DS) << 4) as i16
-                + match base {
-                    BaseRegister::BX => hardware.clone().read_from_word_register(BX) as i16,
-                    BaseRegister::BP => hardware.clone().read_from_word_register(BP) as i16,
-                }
-                + match index {
-                    IndexRegister::DI => hardware.clone().read_from_word_register(DI) as i16,
-                    IndexRegister::SI => hardware.clone().read_from_word_register(SI) as i16,
-                }
-                + match disp {
-                    crate::disassembler::numerical::Displacement::SignedWord(disp) => disp,
-                    crate::disassembler::numerical::Displacement::SignedByte(disp) => {
-                        i16::from(disp)
-                    }
-                    _ => return None,
-                }) as u16;
-            Some(address)
+        &Addressing::BasedIndexedAddressing(base, index, disp) => base_value(base, hardware)
+            .wrapping_add(index_value(index, hardware))
+            .wrapping_add(numerical_value(&disp)),
+    };
+    Some(offset)
+}
+
+pub fn calculate_effective_address(r_m: &Addressing, hardware: &Hardware) -> Option<u32> {
+    match r_m {
+        &Addressing::DirectIndexAddressing(offset, segment) => {
+            Some((u32::from(numerical_value(&segment)) << 4) + u32::from(numerical_value(&offset)))
         }
-        _ => unreachable!(),
+        // BP based modes default to the stack segment, everything else to the data segment
+        &Addressing::BasedAddressing(BaseRegister::BP, _)
+        | &Addressing::BasedIndexedAddressing(BaseRegister::BP, _, _) => {
+            calculate_offset(r_m, hardware).map(|offset| {
+                hardware.physical_address(hardware.segment_override.unwrap_or(SS), offset)
+            })
+        }
+        _ => calculate_offset(r_m, hardware).map(|offset| {
+            hardware.physical_address(hardware.segment_override.unwrap_or(DS), offset)
+        }),
     }
 }
 
@@ -76,29 +109,23 @@ pub fn read_from_address(
     match address {
         &Addressing::RegisterAddressing(reg) => match reg {
             Register::ByteReg(reg) => Some(Immediate::UnsignedByte(
-                hardware.clone().read_from_byte_register(reg),
+                hardware.read_from_byte_register(reg),
             )),
             Register::WordReg(reg) => Some(Immediate::UnsignedWord(
-                hardware.clone().read_from_word_register(reg),
+                hardware.read_from_word_register(reg),
             )),
             Register::SegmentReg(reg) => Some(Immediate::UnsignedWord(
-                hardware.clone().read_from_segment_register(reg),
+                hardware.read_from_segment_register(reg),
             )),
         },
         _ => {
             if let Some(address) = calculate_effective_address(&address, hardware) {
                 match word_mode {
                     true => Some(Immediate::UnsignedWord(
-                        hardware
-                            .clone()
-                            .read_word_from_memory(address)
-                            .expect("Unable to retrieve data"),
+                        hardware.read_word_from_memory(address),
                     )),
                     false => Some(Immediate::UnsignedByte(
-                        hardware
-                            .clone()
-                            .read_byte_from_memory(address)
-                            .expect("Unable to retrieve data"),
+                        hardware.read_byte_from_memory(address),
                     )),
                 }
             } else {
@@ -108,7 +135,11 @@ pub fn read_from_address(
     }
 }
 
-pub fn write_to_address(address: &Addressing, immediate: &Immediate, hardware: &mut Hardware) {
+pub fn write_to_address(
+    address: &Addressing,
+    immediate: &Immediate,
+    hardware: &mut Hardware,
+) -> Result<(), MmvmError> {
     match address {
         &Addressing::RegisterAddressing(reg) => match (reg, immediate) {
             (Register::ByteReg(reg), &Immediate::SignedByte(imme)) => {
@@ -126,23 +157,20 @@ pub fn write_to_address(address: &Addressing, immediate: &Immediate, hardware: &
             (Register::SegmentReg(reg), &Immediate::UnsignedWord(imme)) => {
                 hardware.write_to_segment_register(reg, imme)
             }
-            _ => panic!("Register size and immediate size should be the same"),
+            // register size and immediate size should be the same
+            _ => return Err(unimplemented()),
         },
         _ => {
-            if let Some(addr) = calculate_effective_address(address, hardware) {
-                match immediate {
-                    &Immediate::UnsignedWord(imme) => hardware.write_word_to_memory(addr, imme),
-                    &Immediate::SignedWord(imme) => {
-                        hardware.write_word_to_memory(addr, imme as u16)
-                    }
-                    &Immediate::UnsignedByte(imme) => hardware.write_byte_to_memory(addr, imme),
-                    &Immediate::SignedByte(imme) => hardware.write_byte_to_memory(addr, imme as u8),
-                }
-            } else {
-                panic!("No match address type")
+            let addr = calculate_effective_address(address, hardware).ok_or_else(memory_fault)?;
+            match immediate {
+                &Immediate::UnsignedWord(imme) => hardware.write_word_to_memory(addr, imme),
+                &Immediate::SignedWord(imme) => hardware.write_word_to_memory(addr, imme as u16),
+                &Immediate::UnsignedByte(imme) => hardware.write_byte_to_memory(addr, imme),
+                &Immediate::SignedByte(imme) => hardware.write_byte_to_memory(addr, imme as u8),
             }
         }
     }
+    Ok(())
 }
 
 pub fn write_from_address_to_address(
@@ -150,27 +178,58 @@ pub fn write_from_address_to_address(
     reg: &Addressing,
     r_m: &Addressing,
     hardware: &mut Hardware,
-) {
+) -> Result<(), MmvmError> {
     let word_mode = match reg {
         &Addressing::RegisterAddressing(Register::ByteReg(_)) => false,
         &Addressing::RegisterAddressing(Register::WordReg(_))
         | &Addressing::RegisterAddressing(Register::SegmentReg(_)) => true,
-        _ => unreachable!(),
+        _ => return Err(unimplemented()),
     };
-    match direction {
-        Direction::FromReg => {
-            if let Some(imme) = read_from_address(word_mode, reg, hardware) {
-                write_to_address(&r_m, &imme, hardware)
-            } else {
-                panic!("Nothing in {:?}", reg)
-            }
-        }
-        Direction::ToReg => {
-            if let Some(imme) = read_from_address(word_mode, r_m, hardware) {
-                write_to_address(&reg, &imme, hardware)
-            } else {
-                panic!("Nothing in {:?}", r_m)
-            }
-        }
+    let (source, destination) = match direction {
+        Direction::FromReg => (reg, r_m),
+        Direction::ToReg => (r_m, reg),
+    };
+    let imme = read_from_address(word_mode, source, hardware).ok_or_else(memory_fault)?;
+    write_to_address(destination, &imme, hardware)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::addressing::Addressing;
+    use crate::disassembler::direction::Direction;
+    use crate::disassembler::numerical::{Displacement, Immediate, Numerical};
+    use crate::disassembler::register::ByteRegister::AL;
+    use crate::disassembler::register::Register;
+    use crate::disassembler::register::WordRegister::AX;
+    use crate::error::MmvmError;
+    use crate::interpreter::hardware::Hardware;
+    use crate::interpreter::utils::{write_from_address_to_address, write_to_address};
+
+    #[test]
+    fn test_write_to_address_errors() {
+        let mut hardware = Hardware::new();
+        let al = Addressing::RegisterAddressing(Register::ByteReg(AL));
+        let ax = Addressing::RegisterAddressing(Register::WordReg(AX));
+        let memory =
+            Addressing::DirectAddressing(Numerical::Disp(Displacement::UnsignedWord(0x10)));
+
+        assert_eq!(
+            write_to_address(&al, &Immediate::UnsignedWord(0x1234), &mut hardware),
+            Err(MmvmError::UnimplementedInstruction {
+                address: 0x0000,
+                bytes: Vec::new(),
+            })
+        );
+        assert_eq!(
+            write_from_address_to_address(Direction::FromReg, &memory, &ax, &mut hardware),
+            Err(MmvmError::UnimplementedInstruction {
+                address: 0x0000,
+                bytes: Vec::new(),
+            })
+        );
+
+        write_to_address(&ax, &Immediate::UnsignedWord(0x1234), &mut hardware).unwrap();
+        write_from_address_to_address(Direction::FromReg, &ax, &memory, &mut hardware).unwrap();
+        assert_eq!(hardware.read_word_from_memory(0x10), 0x1234);
     }
 }
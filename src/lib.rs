@@ -1,5 +1,5 @@
-#![feature(bigint_helper_methods)]
 pub mod cli;
 pub mod disassembler;
+pub mod error;
 pub mod interpreter;
 pub mod utils;
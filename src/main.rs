@@ -1,38 +1,43 @@
-#![feature(bigint_helper_methods)]
 extern crate core;
 
-mod cli;
-mod disassembler;
-mod interpreter;
-mod utils;
-
-use crate::disassembler::Disassembler;
-use crate::interpreter::Interpreter;
-use crate::utils::header::Header;
 use clap::Parser;
+use mmvm::disassembler::Disassembler;
+use mmvm::error::MmvmError;
+use mmvm::interpreter::Interpreter;
+use mmvm::utils::header::Header;
 
-use crate::cli::args::Args;
-use crate::utils::file_reader::read_file;
+use mmvm::cli::args::Args;
+use mmvm::utils::file_reader::read_file;
 
 fn main() {
     let cli = Args::parse();
+    if let Err(error) = run(cli) {
+        eprintln!("mmvm: {}", error);
+        std::process::exit(1);
+    }
+}
 
-    let binary_path = if cli.d.is_some() {
-        cli.d.clone()
-    } else {
-        cli.m.clone()
-    };
-    let bytes_data: Vec<u8> = read_file(&binary_path).expect("Failed to read the input file");
-    let header = Header::new(&bytes_data);
-
-    let mut disassembler = Disassembler::new();
-    disassembler.disassemble(header.clone(), &bytes_data);
-
-    let mut interpreter = Interpreter::new();
-
-    if cli.d.is_some() {
+fn run(cli: Args) -> Result<(), MmvmError> {
+    let binary_path = cli
+        .d
+        .as_deref()
+        .or(cli.header.as_deref())
+        .or(cli.m.as_deref())
+        .ok_or(MmvmError::MissingInput)?;
+    let bytes_data: Vec<u8> = read_file(binary_path)?;
+    let header = Header::new(&bytes_data)?;
+
+    if cli.header.is_some() {
+        print!("{}", header);
+    } else if cli.d.is_some() {
+        let mut disassembler = Disassembler::new();
+        disassembler.disassemble(header, &bytes_data)?;
         disassembler.print();
     } else {
-        interpreter.execute(header.clone(), &bytes_data, disassembler.asm);
+        let mut interpreter = Interpreter::new();
+        let args = [vec![binary_path.display().to_string()], cli.args].concat();
+        interpreter.minix.root = cli.root;
+        interpreter.execute(header, &bytes_data, &args, &cli.env)?;
     }
+    Ok(())
 }
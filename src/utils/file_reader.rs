@@ -1,10 +1,41 @@
 use std::fs::File;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
-pub fn read_file(path: &Option<PathBuf>) -> io::Result<Vec<u8>> {
-    let mut file: File = File::open(path.as_deref().unwrap())?;
+use crate::error::MmvmError;
+
+pub fn read_file(path: &Path) -> Result<Vec<u8>, MmvmError> {
+    let io_error = |error: std::io::Error| MmvmError::Io {
+        path: PathBuf::from(path),
+        kind: error.kind(),
+    };
+    let mut file: File = File::open(path).map_err(io_error)?;
     let mut buffer: Vec<u8> = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    file.read_to_end(&mut buffer).map_err(io_error)?;
     Ok(buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    use crate::error::MmvmError;
+    use crate::utils::file_reader::read_file;
+
+    #[test]
+    fn test_read_file_errors() {
+        let path = Path::new("testcases/missing.out");
+        assert_eq!(
+            read_file(path),
+            Err(MmvmError::Io {
+                path: PathBuf::from(path),
+                kind: ErrorKind::NotFound,
+            })
+        );
+        assert_eq!(
+            read_file(Path::new("testcases/1.out")).unwrap()[0..2],
+            [0x01, 0x03]
+        );
+    }
+}
@@ -1,4 +1,18 @@
 #![allow(dead_code)]
+use std::fmt::{Display, Formatter};
+
+use crate::error::MmvmError;
+
+// MINIX a.out magic number
+pub const A_MAGIC: [u8; 2] = [0x01, 0x03];
+// a_cpu of an Intel 8086 executable
+pub const A_I8086: u8 = 0x04;
+// a_flags bit telling that text and data live in separate segments
+pub const A_SEP: u8 = 0x20;
+// a_hdrlen of the short form and of the long form carrying relocation sizes and bases
+pub const A_MINHDR: u8 = 32;
+pub const A_LONGHDR: u8 = 48;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Header {
     pub a_magic: Vec<u8>,
@@ -13,14 +27,52 @@ pub struct Header {
     pub a_entry: u32,
     pub a_total: u32,
     pub a_syms: u32,
+    // long form only, zero in the short form
+    pub a_trsize: u32,
+    pub a_drsize: u32,
+    pub a_tbase: u32,
+    pub a_dbase: u32,
+}
+
+fn long(bytes_data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes((&bytes_data[offset..(offset + 4)]).try_into().unwrap())
 }
 
 impl Header {
-    pub fn new(bytes_data: &[u8]) -> Self {
-        if bytes_data.len() < 32 {
-            panic!("there's no enough length to extract header");
+    pub fn new(bytes_data: &[u8]) -> Result<Self, MmvmError> {
+        if bytes_data.len() < A_MINHDR as usize {
+            return Err(MmvmError::TruncatedInput {
+                expected: A_MINHDR as usize,
+                actual: bytes_data.len(),
+            });
+        }
+        if bytes_data[0..2] != A_MAGIC {
+            return Err(MmvmError::BadMagic(bytes_data[0..2].to_vec()));
+        }
+        if bytes_data[3] != A_I8086 {
+            return Err(MmvmError::UnsupportedCpu(bytes_data[3]));
+        }
+        let a_hdrlen = bytes_data[4];
+        if a_hdrlen != A_MINHDR && a_hdrlen != A_LONGHDR {
+            return Err(MmvmError::BadHeaderLength(a_hdrlen));
         }
-        Self {
+        if bytes_data.len() < a_hdrlen as usize {
+            return Err(MmvmError::TruncatedInput {
+                expected: a_hdrlen as usize,
+                actual: bytes_data.len(),
+            });
+        }
+        let (a_trsize, a_drsize, a_tbase, a_dbase) = if a_hdrlen == A_LONGHDR {
+            (
+                long(bytes_data, 32),
+                long(bytes_data, 36),
+                long(bytes_data, 40),
+                long(bytes_data, 44),
+            )
+        } else {
+            (0, 0, 0, 0)
+        };
+        Ok(Self {
             a_magic: Vec::from(&bytes_data[0..2]),
             a_flags: u8::from(bytes_data[2]),
             a_cpu: u8::from(bytes_data[3]),
@@ -33,12 +85,74 @@ impl Header {
             a_entry: u32::from_le_bytes((&bytes_data[20..24]).try_into().unwrap()),
             a_total: u32::from_le_bytes((&bytes_data[24..28]).try_into().unwrap()),
             a_syms: u32::from_le_bytes((&bytes_data[28..32]).try_into().unwrap()),
+            a_trsize,
+            a_drsize,
+            a_tbase,
+            a_dbase,
+        })
+    }
+
+    pub fn separate_id(&self) -> bool {
+        (self.a_flags & A_SEP) == A_SEP
+    }
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:02x}{:02x}",
+            "a_magic", self.a_magic[0], self.a_magic[1]
+        )?;
+        writeln!(f, "{:<10} 0x{:02x}", "a_flags", self.a_flags)?;
+        writeln!(f, "{:<10} 0x{:02x}", "a_cpu", self.a_cpu)?;
+        writeln!(f, "{:<10} {}", "a_hdrlen", self.a_hdrlen)?;
+        writeln!(f, "{:<10} 0x{:02x}", "a_unused", self.a_unused)?;
+        writeln!(f, "{:<10} 0x{:04x}", "a_version", self.a_version)?;
+        let mut fields = vec![
+            ("a_text", self.a_text),
+            ("a_data", self.a_data),
+            ("a_bss", self.a_bss),
+            ("a_entry", self.a_entry),
+            ("a_total", self.a_total),
+            ("a_syms", self.a_syms),
+        ];
+        if self.a_hdrlen == A_LONGHDR {
+            fields.extend([
+                ("a_trsize", self.a_trsize),
+                ("a_drsize", self.a_drsize),
+                ("a_tbase", self.a_tbase),
+                ("a_dbase", self.a_dbase),
+            ]);
         }
+        for (name, value) in fields {
+            writeln!(f, "{:<10} 0x{:08x} ({})", name, value, value)?;
+        }
+
+        // a_total covers data, bss, heap and stack, plus text when I&D are common
+        let (layout, occupied) = if self.separate_id() {
+            ("separate I&D", self.a_data + self.a_bss)
+        } else {
+            ("common I&D", self.a_text + self.a_data + self.a_bss)
+        };
+        let heap_stack = self.a_total.saturating_sub(occupied);
+        writeln!(f)?;
+        writeln!(f, "{:<10} {}", "layout", layout)?;
+        for (name, value) in [
+            ("text", self.a_text),
+            ("data", self.a_data),
+            ("bss", self.a_bss),
+            ("heap+stack", heap_stack),
+        ] {
+            writeln!(f, "{:<10} 0x{:05x} ({})", name, value, value)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::error::MmvmError;
     use crate::utils::header::Header;
 
     #[test]
@@ -50,7 +164,7 @@ mod test {
         ];
         assert_eq!(
             Header::new(testcases),
-            Header {
+            Ok(Header {
                 a_magic: [0x01, 0x03].to_vec(),
                 a_flags: 0x20,
                 a_cpu: 0x04,
@@ -63,10 +177,72 @@ mod test {
                 a_entry: 0x00000000,
                 a_total: 0x00010000,
                 a_syms: 0x000002c0,
-            }
+                a_trsize: 0x00000000,
+                a_drsize: 0x00000000,
+                a_tbase: 0x00000000,
+                a_dbase: 0x00000000,
+            })
         )
     }
 
+    #[test]
+    fn test_invalid_header() {
+        let mut testcases = vec![0x00; 32];
+        assert_eq!(
+            Header::new(&testcases[..16]),
+            Err(MmvmError::TruncatedInput {
+                expected: 32,
+                actual: 16
+            })
+        );
+        testcases[0] = 0x7f;
+        testcases[1] = 0x45;
+        assert_eq!(
+            Header::new(&testcases),
+            Err(MmvmError::BadMagic(vec![0x7f, 0x45]))
+        );
+        testcases[0..5].copy_from_slice(&[0x01, 0x03, 0x20, 0x10, 0x20]);
+        assert_eq!(
+            Header::new(&testcases),
+            Err(MmvmError::UnsupportedCpu(0x10))
+        );
+        testcases[3..5].copy_from_slice(&[0x04, 0x40]);
+        assert_eq!(
+            Header::new(&testcases),
+            Err(MmvmError::BadHeaderLength(0x40))
+        );
+        testcases[4] = 0x30;
+        assert_eq!(
+            Header::new(&testcases),
+            Err(MmvmError::TruncatedInput {
+                expected: 48,
+                actual: 32
+            })
+        );
+    }
+
+    #[test]
+    fn test_long_header() {
+        let mut testcases = vec![0x01, 0x03, 0x00, 0x04, 0x30, 0x00, 0x00, 0x00];
+        testcases.extend([0x00; 24]);
+        testcases.extend([
+            0x10, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00,
+        ]);
+        let header = Header::new(&testcases).unwrap();
+        assert_eq!(header.a_hdrlen, 48);
+        assert_eq!(
+            (
+                header.a_trsize,
+                header.a_drsize,
+                header.a_tbase,
+                header.a_dbase
+            ),
+            (0x10, 0x20, 0x100, 0x200)
+        );
+        assert!(!header.separate_id());
+    }
+
     #[test]
     fn test_binary_data() {}
 }
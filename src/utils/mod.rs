@@ -1,2 +1,3 @@
 pub mod file_reader;
 pub mod header;
+pub mod symbol;
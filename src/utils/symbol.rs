@@ -0,0 +1,95 @@
+use crate::error::MmvmError;
+use crate::utils::header::Header;
+
+// size of a MINIX `struct nlist` entry: n_name[8], n_value, n_sclass, n_numaux, n_type
+pub const NLIST_SIZE: usize = 16;
+// n_sclass keeps the section in its low bits and the storage class above them
+pub const N_SECT: u8 = 0o07;
+pub const N_TEXT: u8 = 0o02;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u32,
+    pub sclass: u8,
+}
+
+impl Symbol {
+    pub fn new(entry: &[u8]) -> Self {
+        let name = entry[0..8]
+            .iter()
+            .take_while(|&&byte| byte != 0x00)
+            .map(|&byte| char::from(byte))
+            .collect::<String>();
+        Self {
+            name,
+            value: u32::from_le_bytes((&entry[8..12]).try_into().unwrap()),
+            sclass: entry[12],
+        }
+    }
+
+    pub fn section(&self) -> u8 {
+        self.sclass & N_SECT
+    }
+}
+
+// the table follows text, data and, in the long form, their relocation entries
+pub fn read_symbols(header: &Header, bytes_data: &[u8]) -> Result<Vec<Symbol>, MmvmError> {
+    let offset = header.a_hdrlen as usize
+        + header.a_text as usize
+        + header.a_data as usize
+        + header.a_trsize as usize
+        + header.a_drsize as usize;
+    let a_syms = header.a_syms as usize;
+    if bytes_data.len() < offset + a_syms {
+        return Err(MmvmError::TruncatedInput {
+            expected: offset + a_syms,
+            actual: bytes_data.len(),
+        });
+    }
+    Ok(bytes_data[offset..(offset + a_syms)]
+        .chunks_exact(NLIST_SIZE)
+        .map(Symbol::new)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::header::Header;
+    use crate::utils::symbol::{read_symbols, Symbol, N_TEXT};
+
+    #[test]
+    fn test_read_symbols() {
+        let mut bytes_data = vec![0x01, 0x03, 0x20, 0x04, 0x20, 0x00, 0x00, 0x00];
+        bytes_data.extend([0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        bytes_data.extend([0x00; 12]);
+        bytes_data.extend([0x30, 0x00, 0x00, 0x00]);
+        bytes_data.extend([0x90, 0xf4, 0x00]);
+        bytes_data.extend(b"_main\0\0\0");
+        bytes_data.extend([0x01, 0x00, 0x00, 0x00, 0o22, 0x00, 0x00, 0x00]);
+        bytes_data.extend(b"_environ");
+        bytes_data.extend([0x00, 0x00, 0x00, 0x00, 0o23, 0x00, 0x00, 0x00]);
+        bytes_data.extend(b"__end\0\0\0");
+        bytes_data.extend([0x04, 0x00, 0x00, 0x00, 0o24, 0x00, 0x00, 0x00]);
+        let header = Header::new(&bytes_data).unwrap();
+
+        let symbols = read_symbols(&header, &bytes_data).unwrap();
+        assert_eq!(
+            symbols[0],
+            Symbol {
+                name: "_main".to_string(),
+                value: 0x0001,
+                sclass: 0o22,
+            }
+        );
+        assert_eq!(symbols[1].name, "_environ");
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| symbol.section())
+                .collect::<Vec<u8>>(),
+            vec![N_TEXT, 0o03, 0o04]
+        );
+        assert!(read_symbols(&header, &bytes_data[..40]).is_err());
+    }
+}
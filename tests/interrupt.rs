@@ -0,0 +1,30 @@
+use mmvm::interpreter::{Hardware, Interpreter, Minix};
+
+#[test]
+fn test_register_host_handler() {
+    let mut interpreter = Interpreter::new();
+    interpreter.trace = false;
+    // int 60 / int 60 / hlt
+    let code = [0xcd, 0x60, 0xcd, 0x60, 0xf4];
+    interpreter.hardware.cs = 0x1000;
+    for (offset, &byte) in code.iter().enumerate() {
+        interpreter
+            .hardware
+            .write_byte_to_memory(0x10000 + offset as u32, byte);
+    }
+
+    // a handler that counts its calls and hands the count back in AX
+    let mut count = 0x0000;
+    interpreter
+        .interrupts
+        .register(0x60, move |hardware: &mut Hardware, _: &mut Minix| {
+            count += 1;
+            hardware.ax = count;
+            Ok(())
+        });
+
+    while !interpreter.hardware.halted {
+        interpreter.step().unwrap();
+    }
+    assert_eq!(interpreter.hardware.ax, 0x0002);
+}
@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mmvm::interpreter::port::Device;
+use mmvm::interpreter::Interpreter;
+
+// a 16-bit data register that latches whole words, the byte halves are never split out
+struct Loopback {
+    accesses: Rc<RefCell<Vec<&'static str>>>,
+    data: u16,
+}
+
+impl Device for Loopback {
+    fn read_byte(&mut self, _: u16) -> u8 {
+        self.accesses.borrow_mut().push("read_byte");
+        self.data as u8
+    }
+
+    fn write_byte(&mut self, _: u16, value: u8) {
+        self.accesses.borrow_mut().push("write_byte");
+        self.data = u16::from(value);
+    }
+
+    fn read_word(&mut self, _: u16) -> u16 {
+        self.accesses.borrow_mut().push("read_word");
+        self.data
+    }
+
+    fn write_word(&mut self, _: u16, value: u16) {
+        self.accesses.borrow_mut().push("write_word");
+        self.data = value;
+    }
+}
+
+#[test]
+fn test_attach_device() {
+    let accesses = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    interpreter.trace = false;
+    interpreter.ports.attach(
+        0x0080..=0x0080,
+        Box::new(Loopback {
+            accesses: accesses.clone(),
+            data: 0x0000,
+        }),
+    );
+    // mov ax, beef / out 80, ax / xor ax, ax / in ax, 80 / in al, 80 / hlt
+    let code = [
+        0xb8, 0xef, 0xbe, 0xe7, 0x80, 0x31, 0xc0, 0xe5, 0x80, 0xe4, 0x80, 0xf4,
+    ];
+    interpreter.hardware.cs = 0x1000;
+    for (offset, &byte) in code.iter().enumerate() {
+        interpreter
+            .hardware
+            .write_byte_to_memory(0x10000 + offset as u32, byte);
+    }
+
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.hardware.ax, 0xbeef);
+    while !interpreter.hardware.halted {
+        interpreter.step().unwrap();
+    }
+    assert_eq!(interpreter.hardware.ax, 0xbeef);
+    assert_eq!(*accesses.borrow(), ["write_word", "read_word", "read_byte"]);
+}